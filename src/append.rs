@@ -0,0 +1,286 @@
+use crate::helpers::write_json_atomically;
+
+use alloy::primitives::TxHash;
+use chrono::NaiveDateTime;
+use eyre::{eyre, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
+
+/// Format of the dates written in the Koinly CSV file
+const DATE_FORMAT: &str = "%Y-%m-%d %H:%M:%S UTC";
+
+/// Columns of a Koinly CSV row needed to append to it
+#[derive(Deserialize)]
+struct KoinlyRow {
+    #[serde(rename = "Date")]
+    date: String,
+    #[serde(rename = "TxHash")]
+    tx_hash: TxHash,
+}
+
+/// Identifies a row by the log it was produced from, a log or failed transaction producing several rows
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct RowKey {
+    pub tx_hash: TxHash,
+    /// Log index of the row, `None` for failed transactions
+    pub log_index: Option<u64>,
+    /// Position of the row among the rows of its log written to the same file
+    pub row: usize,
+}
+
+/// Rows written to a Koinly CSV file at its last date. Blocks sharing the date of the last row are
+/// scanned again when appending, so these rows are skipped instead of being written twice.
+/// They are saved next to the CSV file once the export completes, and in its checkpoint meanwhile.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WrittenRows {
+    /// Date of the last row written
+    date: String,
+    rows: BTreeSet<RowKey>,
+}
+
+impl WrittenRows {
+    /// Path of the rows written to a given CSV file
+    pub fn path(output_path: &str) -> PathBuf {
+        PathBuf::from(format!(
+            "{}.rows.json",
+            output_path.trim_end_matches(".csv")
+        ))
+    }
+
+    /// Load the rows written to a CSV file, `None` if they were never saved
+    pub fn load(output_path: &str) -> Result<Option<Self>> {
+        let path = Self::path(output_path);
+
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(eyre!("Failed to read {}: {}", path.display(), error)),
+        };
+
+        serde_json::from_str(&content)
+            .map(Some)
+            .map_err(|error| eyre!("Failed to parse {}: {}", path.display(), error))
+    }
+
+    pub fn save(&self, output_path: &str) -> Result<()> {
+        let path = Self::path(output_path);
+
+        write_json_atomically(&path, self)
+            .map_err(|error| eyre!("Failed to write {}: {}", path.display(), error))
+    }
+
+    /// Record a row, forgetting the rows of the previous dates since rows are written in order
+    pub fn push(&mut self, date: &str, key: RowKey) {
+        if self.date != date {
+            self.date = date.to_string();
+            self.rows.clear();
+        }
+
+        self.rows.insert(key);
+    }
+}
+
+/// Rows already written to a Koinly CSV file, so that an incremental export only appends new ones
+#[derive(Default)]
+pub struct ExistingRows {
+    /// Columns of the file, empty if the file is empty
+    pub headers: Vec<String>,
+    /// Timestamp of the last row
+    pub last_timestamp: Option<u64>,
+    /// Number of rows in the file
+    len: usize,
+    /// Rows written at the date of the last row
+    pub written: WrittenRows,
+    /// Transactions of the rows at the date of the last row of a file exported before the rows
+    /// were saved next to it, whose rows are all skipped
+    unsaved_tx_hashes: HashSet<TxHash>,
+}
+
+impl ExistingRows {
+    /// Read the rows of a Koinly CSV file, a missing file has no rows.
+    /// The rows written at its last date are taken from `written` if given, e.g. by a checkpoint,
+    /// and from the file saved next to it otherwise.
+    pub fn from_koinly_csv(output_path: &str, written: Option<WrittenRows>) -> Result<Self> {
+        let path = Path::new(output_path);
+
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let mut reader = csv::Reader::from_path(path)
+            .map_err(|error| eyre!("Failed to open {}: {}", path.display(), error))?;
+
+        let mut rows = Self {
+            headers: reader
+                .headers()
+                .map_err(|error| eyre!("Failed to read {}: {}", path.display(), error))?
+                .iter()
+                .map(str::to_string)
+                .collect(),
+            ..Self::default()
+        };
+        let mut last_date: Option<String> = None;
+        let mut last_date_tx_hashes = HashSet::new();
+
+        for row in reader.deserialize() {
+            let row: KoinlyRow =
+                row.map_err(|error| eyre!("Failed to read {}: {}", path.display(), error))?;
+
+            let timestamp = NaiveDateTime::parse_from_str(&row.date, DATE_FORMAT)
+                .map_err(|_| {
+                    eyre!(
+                        "Failed to parse date \"{}\" in {}",
+                        row.date,
+                        path.display()
+                    )
+                })?
+                .and_utc()
+                .timestamp() as u64;
+
+            rows.last_timestamp = rows.last_timestamp.max(Some(timestamp));
+            rows.len += 1;
+
+            if last_date.as_ref() != Some(&row.date) {
+                last_date_tx_hashes.clear();
+            }
+
+            last_date_tx_hashes.insert(row.tx_hash);
+            last_date = Some(row.date);
+        }
+
+        let Some(last_date) = last_date else {
+            return Ok(rows);
+        };
+
+        let written = match written {
+            Some(written) => Some(written),
+            None => WrittenRows::load(output_path)?,
+        };
+
+        match written {
+            Some(written) if written.date == last_date => rows.written = written,
+            Some(_) => {
+                return Err(eyre!(
+                "Rows written to CSV file {} at {} are unknown, export it again without --append",
+                output_path,
+                last_date
+            ))
+            }
+            None => {
+                log::warn!(
+                    "CSV file {} was exported without saving its rows, the rows of the transactions at {} are skipped",
+                    output_path,
+                    last_date
+                );
+
+                rows.unsaved_tx_hashes = last_date_tx_hashes;
+            }
+        }
+
+        Ok(rows)
+    }
+
+    /// Number of rows in the file
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Whether a row was already written, in which case it is skipped
+    pub fn contains(&self, key: &RowKey) -> bool {
+        self.written.rows.contains(key) || self.unsaved_tx_hashes.contains(&key.tx_hash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(log_index: u64, row: usize) -> RowKey {
+        RowKey {
+            tx_hash: TxHash::with_last_byte(1),
+            log_index: Some(log_index),
+            row,
+        }
+    }
+
+    #[test]
+    fn keeps_rows_of_the_last_date() {
+        let mut written = WrittenRows::default();
+
+        written.push("2024-01-01 00:00:00 UTC", key(1, 0));
+        written.push("2024-01-01 00:00:02 UTC", key(2, 0));
+        written.push("2024-01-01 00:00:02 UTC", key(2, 1));
+
+        assert_eq!(written.date, "2024-01-01 00:00:02 UTC");
+        assert_eq!(written.rows, BTreeSet::from([key(2, 0), key(2, 1)]));
+    }
+
+    #[test]
+    fn skips_rows_written_at_the_last_date() {
+        let dir = std::env::temp_dir().join(format!("append-{}", rand::random::<u64>()));
+        let output_path = dir.join("output.csv").to_string_lossy().to_string();
+
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            &output_path,
+            format!(
+                "Date,TxHash\n2024-01-01 00:00:00 UTC,{0}\n2024-01-01 00:00:02 UTC,{0}\n",
+                TxHash::with_last_byte(1)
+            ),
+        )
+        .unwrap();
+
+        // Rows saved for another date than the last row are stale.
+        let mut written = WrittenRows::default();
+        written.push("2024-01-01 00:00:00 UTC", key(1, 0));
+        written.save(&output_path).unwrap();
+
+        assert!(ExistingRows::from_koinly_csv(&output_path, None).is_err());
+
+        written.push("2024-01-01 00:00:02 UTC", key(2, 0));
+        written.save(&output_path).unwrap();
+
+        let rows = ExistingRows::from_koinly_csv(&output_path, None).unwrap();
+
+        assert_eq!(rows.len(), 2);
+        assert!(rows.contains(&key(2, 0)));
+        // Blocks before the last date aren't scanned again, so their rows are forgotten.
+        assert!(!rows.contains(&key(1, 0)));
+        assert!(!rows.contains(&key(2, 1)));
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn skips_transactions_of_files_without_saved_rows() {
+        let dir = std::env::temp_dir().join(format!("append-{}", rand::random::<u64>()));
+        let output_path = dir.join("output.csv").to_string_lossy().to_string();
+
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            &output_path,
+            format!(
+                "Date,TxHash\n2024-01-01 00:00:00 UTC,{}\n2024-01-01 00:00:02 UTC,{}\n",
+                TxHash::with_last_byte(2),
+                TxHash::with_last_byte(1)
+            ),
+        )
+        .unwrap();
+
+        let rows = ExistingRows::from_koinly_csv(&output_path, None).unwrap();
+
+        assert!(rows.contains(&key(5, 3)));
+        assert!(!rows.contains(&RowKey {
+            tx_hash: TxHash::with_last_byte(2),
+            log_index: Some(0),
+            row: 0
+        }));
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+}
@@ -0,0 +1,285 @@
+use crate::log_info_cyan;
+
+use alloy::{
+    network::{primitives::BlockTransactionsKind, AnyNetwork},
+    providers::Provider,
+    rpc::types::BlockNumberOrTag,
+    transports::Transport,
+};
+use colored::Colorize;
+use eyre::{eyre, Result};
+use foundry_block_explorers::Client;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+/// Number of blocks behind the latest block considered safe from reorgs
+/// when the provider does not support the `finalized` block tag
+const REORG_SAFETY_BLOCKS: u64 = 1000;
+
+/// Resolves block numbers from timestamps by binary searching block headers through the provider.
+/// An Etherscan client can optionally be supplied to get a starting hint, which is always verified
+/// against the provider before being used.
+#[derive(Clone)]
+pub struct BlockResolver<T, P> {
+    /// Provider
+    provider: Arc<P>,
+    /// Optional Etherscan client used as a fast path
+    etherscan: Option<Client>,
+    phantom: PhantomData<T>,
+}
+
+impl<T, P> BlockResolver<T, P>
+where
+    T: Transport + Clone,
+    P: Provider<T, AnyNetwork>,
+{
+    pub fn new(provider: Arc<P>, etherscan: Option<Client>) -> Self {
+        Self {
+            provider,
+            etherscan,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Get the timestamp of a given block number
+    pub async fn get_block_timestamp(&self, block_number: u64) -> Result<u64> {
+        let block = self
+            .provider
+            .get_block(block_number.into(), BlockTransactionsKind::Hashes)
+            .await?
+            .ok_or_else(|| eyre!("Block {} not found", block_number))?;
+
+        Ok(block.header.timestamp)
+    }
+
+    /// Get the last finalized block, falling back to the latest block minus `REORG_SAFETY_BLOCKS`
+    /// on providers that do not support the `finalized` block tag
+    pub async fn get_finalized_block(&self) -> Result<u64> {
+        if let Ok(Some(block)) = self
+            .provider
+            .get_block(
+                BlockNumberOrTag::Finalized.into(),
+                BlockTransactionsKind::Hashes,
+            )
+            .await
+        {
+            return Ok(block.header.number);
+        }
+
+        Ok(self
+            .provider
+            .get_block_number()
+            .await?
+            .saturating_sub(REORG_SAFETY_BLOCKS))
+    }
+
+    /// Get the first block whose timestamp is greater than or equal to `timestamp`
+    pub async fn first_block_at_or_after(&self, timestamp: u64) -> Result<u64> {
+        let latest_block = self.provider.get_block_number().await?;
+
+        if self.get_block_timestamp(latest_block).await? < timestamp {
+            return Err(eyre!(
+                "Timestamp {} is after the latest block {}",
+                timestamp,
+                latest_block
+            ));
+        }
+
+        if let Some(block_number) = self.etherscan_hint(timestamp, latest_block).await {
+            return Ok(block_number);
+        }
+
+        // Invariant: the block at `high` is at or after `timestamp`, blocks before `low` are not.
+        let (mut low, mut high) = (0, latest_block);
+
+        while low < high {
+            let middle = low + (high - low) / 2;
+
+            if self.get_block_timestamp(middle).await? >= timestamp {
+                high = middle;
+            } else {
+                low = middle + 1;
+            }
+        }
+
+        Ok(high)
+    }
+
+    /// Get the last block whose timestamp is strictly lower than `timestamp`
+    pub async fn last_block_before(&self, timestamp: u64) -> Result<u64> {
+        let latest_block = self.provider.get_block_number().await?;
+
+        // If the chain has not reached `timestamp` yet, the latest block is the last one before it.
+        if self.get_block_timestamp(latest_block).await? < timestamp {
+            return Ok(latest_block);
+        }
+
+        match self.first_block_at_or_after(timestamp).await? {
+            0 => Err(eyre!("No block found before timestamp {}", timestamp)),
+            block_number => Ok(block_number - 1),
+        }
+    }
+
+    /// Resolve the inclusive block range covering `[start_timestamp, end_timestamp)`
+    pub async fn get_block_range(
+        &self,
+        start_timestamp: u64,
+        end_timestamp: u64,
+    ) -> Result<(u64, u64)> {
+        if start_timestamp >= end_timestamp {
+            return Err(eyre!(
+                "Start timestamp {} must be lower than end timestamp {}",
+                start_timestamp,
+                end_timestamp
+            ));
+        }
+
+        let from_block = self.first_block_at_or_after(start_timestamp).await?;
+        let to_block = self.last_block_before(end_timestamp).await?;
+
+        if from_block > to_block {
+            return Err(eyre!(
+                "No block found between timestamps {} and {}",
+                start_timestamp,
+                end_timestamp
+            ));
+        }
+
+        Ok((from_block, to_block))
+    }
+
+    /// Ask Etherscan for the block closest after `timestamp` and only return it
+    /// if it is exactly the first block at or after `timestamp`.
+    async fn etherscan_hint(&self, timestamp: u64, latest_block: u64) -> Option<u64> {
+        let etherscan = self.etherscan.as_ref()?;
+
+        let block_number: u64 = etherscan
+            .get_block_by_timestamp(timestamp, "after")
+            .await
+            .ok()?
+            .block_number
+            .as_number()?
+            .try_into()
+            .ok()?;
+
+        if block_number > latest_block
+            || self.get_block_timestamp(block_number).await.ok()? < timestamp
+        {
+            return None;
+        }
+
+        if block_number > 0 && self.get_block_timestamp(block_number - 1).await.ok()? >= timestamp {
+            log_info_cyan!(
+                "Etherscan block {} is not the first block at timestamp {}, falling back to binary search",
+                block_number,
+                timestamp
+            );
+
+            return None;
+        }
+
+        Some(block_number)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_rpc::{block, quantity, MockResponse, MockRpc};
+
+    use alloy::{
+        providers::{ProviderBuilder, RootProvider},
+        transports::http::{Client as HttpClient, Http},
+    };
+    use serde_json::json;
+
+    type MockTransport = Http<HttpClient>;
+    type MockProvider = RootProvider<MockTransport, AnyNetwork>;
+
+    /// Timestamp of the genesis block of the mock chain
+    const GENESIS_TIMESTAMP: u64 = 1_000;
+    /// Latest block of the mock chain
+    const LATEST_BLOCK: u64 = 100;
+
+    /// Block resolver over a mock chain producing a block every 2 seconds
+    async fn resolver() -> (MockRpc, BlockResolver<MockTransport, MockProvider>) {
+        let rpc = MockRpc::start(|method, params, _| match method {
+            "eth_blockNumber" => MockResponse::Result(json!(format!("0x{:x}", LATEST_BLOCK))),
+            "eth_getBlockByNumber" => {
+                let number = quantity(&params[0]);
+
+                MockResponse::Result(if number <= LATEST_BLOCK {
+                    block(number, GENESIS_TIMESTAMP + 2 * number)
+                } else {
+                    json!(null)
+                })
+            }
+            _ => MockResponse::Error(-32601, format!("unsupported method {}", method)),
+        })
+        .await;
+
+        let provider = ProviderBuilder::new()
+            .network::<AnyNetwork>()
+            .on_http(rpc.url.clone());
+
+        (rpc, BlockResolver::new(Arc::new(provider), None))
+    }
+
+    #[tokio::test]
+    async fn resolves_exact_timestamps() {
+        let (_rpc, resolver) = resolver().await;
+
+        // The start block is the block mined at the start timestamp, the end block is the one
+        // before the block mined at the end timestamp.
+        assert_eq!(
+            resolver.get_block_range(1_010, 1_020).await.unwrap(),
+            (5, 9)
+        );
+    }
+
+    #[tokio::test]
+    async fn resolves_timestamps_between_blocks() {
+        let (_rpc, resolver) = resolver().await;
+
+        assert_eq!(
+            resolver.get_block_range(1_011, 1_021).await.unwrap(),
+            (6, 10)
+        );
+    }
+
+    #[tokio::test]
+    async fn resolves_genesis_block() {
+        let (_rpc, resolver) = resolver().await;
+
+        assert_eq!(
+            resolver
+                .get_block_range(0, GENESIS_TIMESTAMP + 1)
+                .await
+                .unwrap(),
+            (0, 0)
+        );
+        assert!(resolver
+            .get_block_range(0, GENESIS_TIMESTAMP)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn resolves_timestamps_after_head() {
+        let (_rpc, resolver) = resolver().await;
+        let head_timestamp = GENESIS_TIMESTAMP + 2 * LATEST_BLOCK;
+
+        // An end timestamp after the head ends the range at the latest block.
+        assert_eq!(
+            resolver
+                .get_block_range(head_timestamp, head_timestamp + 3_600)
+                .await
+                .unwrap(),
+            (LATEST_BLOCK, LATEST_BLOCK)
+        );
+        assert!(resolver
+            .get_block_range(head_timestamp + 1, head_timestamp + 3_600)
+            .await
+            .is_err());
+    }
+}
@@ -1,20 +1,27 @@
 use crate::{
+    cache::{Cache, CachedBlock},
     config::Config,
-    constants::{
-        get_asset_decimals, get_asset_symbol, get_underlying_asset_address, POOL_OPTIMISM_ADDRESS,
-    },
+    discovery::PairResolver,
+    errors::BotError,
     log_info_cyan,
+    prices::PriceResolver,
+    registry::{ChainRegistry, TokenInfo},
+    tokens::TokenResolver,
+    transactions::FailedTransaction,
+    transfers::{pair_net_transfers, TokenAmounts, TokenFlows, TransferMismatch},
+    wallets::{ExportMode, MatchOn},
 };
 
 use alloy::{
     network::{primitives::BlockTransactionsKind, AnyNetwork},
-    primitives::{utils::format_units, Address, U64},
+    primitives::{utils::format_units, Address, TxHash, B256, I256, U256},
     providers::{
         fillers::{ChainIdFiller, FillProvider, GasFiller, JoinFill, NonceFiller},
         Identity, Provider, RootProvider,
     },
     rpc::types::{AnyTransactionReceipt, Log},
     sol,
+    sol_types::SolEvent,
     transports::{
         http::{Client, Http},
         Transport,
@@ -25,7 +32,46 @@ use colored::Colorize;
 use op_alloy_rpc_types::OptimismTransactionReceiptFields;
 use serde::Serialize;
 use std::marker::PhantomData;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+
+/// Columns of the rows, in the order of the fields of `KoinlyData`
+pub const KOINLY_HEADERS: [&str; 9] = [
+    "Date",
+    "Sent Amount",
+    "Sent Currency",
+    "Received Amount",
+    "Received Currency",
+    "Fee Amount",
+    "Fee Currency",
+    "Label",
+    "TxHash",
+];
+
+/// Columns of the value of the rows, only written when prices are configured so that files
+/// exported without them keep the same columns
+pub const NET_WORTH_HEADERS: [&str; 3] = ["Net Worth Amount", "Net Worth Currency", "Description"];
+
+/// Columns of the rows of a CSV file, followed by the net worth columns when rows are valued
+/// and by the Wallet column in combined files
+pub fn koinly_headers(valued: bool, wallet_column: bool) -> Vec<String> {
+    KOINLY_HEADERS
+        .iter()
+        .chain(NET_WORTH_HEADERS.iter().filter(|_| valued))
+        .chain(wallet_column.then_some(&"Wallet"))
+        .map(|header| header.to_string())
+        .collect()
+}
+
+/// Koinly label of the rows recording the fees of reverted liquidations, and the tokens only
+/// sent by liquidations recorded from their net transfers
+const COST_LABEL: &str = "cost";
+
+/// Koinly label of the rows recording the prize claiming fees and draw rewards earned by bots
+const REWARD_LABEL: &str = "reward";
+
+/// Koinly label of the rows recording the prizes won by depositors, and the tokens only
+/// received by liquidations recorded from their net transfers
+const INCOME_LABEL: &str = "income";
 
 #[derive(Clone, Serialize)]
 pub struct KoinlyData {
@@ -43,12 +89,170 @@ pub struct KoinlyData {
     fee: String,
     #[serde(rename = "Fee Currency")]
     fee_symbol: String,
+    /// Koinly label of the row, empty for liquidations which are trades
+    #[serde(rename = "Label")]
+    label: String,
     #[serde(rename = "TxHash")]
     tx_hash: String,
+    /// Value of the row in the net worth currency, empty if it is not valued.
+    /// Written as `NetWorthColumns` when prices are configured.
+    #[serde(skip)]
+    net_worth: String,
+    #[serde(skip)]
+    net_worth_symbol: String,
+    /// Source of the price used to value the row
+    #[serde(skip)]
+    description: String,
+    /// Wallet the row belongs to, used to route it to its CSV file
+    #[serde(skip)]
+    pub wallet: Address,
+}
+
+impl KoinlyData {
+    pub fn date(&self) -> &str {
+        &self.date
+    }
+}
+
+/// Columns appended to the rows when prices are configured
+#[derive(Serialize)]
+struct NetWorthColumns<'a> {
+    #[serde(rename = "Net Worth Amount")]
+    net_worth: &'a str,
+    #[serde(rename = "Net Worth Currency")]
+    net_worth_symbol: &'a str,
+    #[serde(rename = "Description")]
+    description: &'a str,
+}
+
+/// Column appended to the rows of a CSV file combining several wallets
+#[derive(Serialize)]
+struct WalletColumn<'a> {
+    #[serde(rename = "Wallet")]
+    wallet: &'a str,
+}
+
+/// Logs or transaction exported as rows
+pub enum ExportItem {
+    /// SwappedExactAmountOut log of the liquidation router
+    Liquidation(Log),
+    /// SwappedExactAmountOut log of a liquidation pair swapped directly
+    PairLiquidation(Log),
+    /// Reward logs of a transaction: prize claims and draw rewards
+    Rewards(Vec<Log>),
+    /// Deposit or Withdraw log of an ERC-4626 vault
+    Vault(Log),
+    FailedTransaction(FailedTransaction),
+}
+
+impl ExportItem {
+    /// Items of logs sorted by block and log index, the reward logs of a transaction being
+    /// grouped so that the rewards it paid are recorded in a single row per wallet, carrying the
+    /// transaction fee once. Reward logs are grouped even when other logs come between them.
+    pub fn from_logs(logs: Vec<Log>) -> Vec<Self> {
+        let mut items = Vec::new();
+
+        for log in logs {
+            let topic0 = log.topics().first().copied().unwrap_or_default();
+
+            if topic0 == Deposit::SIGNATURE_HASH || topic0 == Withdraw::SIGNATURE_HASH {
+                items.push(ExportItem::Vault(log));
+                continue;
+            }
+
+            if topic0 == LiquidationPair::SwappedExactAmountOut::SIGNATURE_HASH {
+                items.push(ExportItem::PairLiquidation(log));
+                continue;
+            }
+
+            if !REWARD_EVENTS.contains(&topic0) {
+                items.push(ExportItem::Liquidation(log));
+                continue;
+            }
+
+            let rewards = items.iter_mut().find_map(|item| match item {
+                ExportItem::Rewards(rewards)
+                    if rewards[0].transaction_hash == log.transaction_hash =>
+                {
+                    Some(rewards)
+                }
+                _ => None,
+            });
+
+            match rewards {
+                Some(rewards) => rewards.push(log),
+                None => items.push(ExportItem::Rewards(vec![log])),
+            }
+        }
+
+        items
+    }
+
+    /// Block, transaction index and log index of the item, rows being written in this order
+    pub fn position(&self) -> (Option<u64>, Option<u64>, Option<u64>) {
+        match self {
+            ExportItem::Liquidation(log)
+            | ExportItem::PairLiquidation(log)
+            | ExportItem::Vault(log) => (log.block_number, log.transaction_index, log.log_index),
+            ExportItem::Rewards(logs) => (
+                logs[0].block_number,
+                logs[0].transaction_index,
+                logs[0].log_index,
+            ),
+            ExportItem::FailedTransaction(transaction) => (
+                Some(transaction.block_number),
+                Some(transaction.transaction_index),
+                None,
+            ),
+        }
+    }
+
+    pub fn tx_hash(&self) -> Option<TxHash> {
+        match self {
+            ExportItem::Liquidation(log)
+            | ExportItem::PairLiquidation(log)
+            | ExportItem::Vault(log) => log.transaction_hash,
+            ExportItem::Rewards(logs) => logs[0].transaction_hash,
+            ExportItem::FailedTransaction(transaction) => Some(transaction.tx_hash),
+        }
+    }
 }
 
 sol! {
+    event AuctionRewardAllocated(uint32 indexed sequenceId, address indexed recipient, uint32 index, uint256 reward);
+    event ClaimedPrize(address indexed vault, address indexed winner, address indexed recipient, uint24 drawId, uint8 tier, uint32 prizeIndex, uint152 payout, uint96 fee, address feeRecipient);
+    event DrawFinished(address indexed sender, address indexed recipient, uint24 indexed drawId, uint48 elapsedTime, uint256 reward, uint256 remainingReserve);
+    event Deposit(address indexed sender, address indexed owner, uint256 assets, uint256 shares);
+    event DrawStarted(address indexed sender, address indexed recipient, uint24 indexed drawId, uint48 elapsedTime, uint256 reward, uint32 rngRequestId, uint64 count);
     event SwappedExactAmountOut(address indexed liquidationPair, address indexed sender, address indexed receiver, uint256 amountOut, uint256 amountInMax, uint256 amountIn, uint256 deadline);
+    event Withdraw(address indexed sender, address indexed receiver, address indexed owner, uint256 assets, uint256 shares);
+
+    /// Liquidation pair, emitting its own event when swapped directly or through the router
+    interface LiquidationPair {
+        event SwappedExactAmountOut(address indexed sender, address indexed receiver, uint256 amountOut, uint256 amountInMax, uint256 amountIn, bytes flashSwapData);
+    }
+}
+
+/// Events paying prize tokens to bots, recorded as rewards
+const REWARD_EVENTS: [B256; 4] = [
+    AuctionRewardAllocated::SIGNATURE_HASH,
+    ClaimedPrize::SIGNATURE_HASH,
+    DrawFinished::SIGNATURE_HASH,
+    DrawStarted::SIGNATURE_HASH,
+];
+
+/// Liquidation decoded from a router or pair event
+#[derive(Clone)]
+struct Liquidation {
+    /// Wallet the liquidation belongs to
+    wallet: Address,
+    sender: Address,
+    receiver: Address,
+    liquidation_pair: Address,
+    /// Amount of prize tokens sent
+    amount_in: U256,
+    /// Amount of the token out of the pair received
+    amount_out: U256,
 }
 
 type Filler = FillProvider<
@@ -62,10 +266,30 @@ type Filler = FillProvider<
 pub struct Bot<F, T, P, W> {
     /// Provider
     provider: Arc<P>,
-    /// Chain ID
-    chain_id: U64,
-    /// Address of the sender for which to record liquidation transactions
-    sender: Address,
+    /// Registry of the chain
+    registry: ChainRegistry,
+    /// Resolver of the liquidation pairs underlying assets
+    pairs: Arc<PairResolver<T, P>>,
+    /// Resolver of the tokens symbols and decimals
+    tokens: Arc<TokenResolver<T, P>>,
+    /// Resolver of the prices used to value the rows
+    prices: Arc<PriceResolver<T, P>>,
+    /// On-disk cache of blocks and receipts
+    cache: Arc<Cache>,
+    /// Addresses of the wallets for which to record liquidation transactions
+    wallets: Vec<Address>,
+    /// Address of the liquidations matched against the wallets
+    match_on: MatchOn,
+    /// Activity of the wallets that is exported
+    export_mode: ExportMode,
+    /// Whether liquidations are recorded from the net token flows of their transaction
+    net_transfers: bool,
+    /// Liquidations whose amounts differ from the net transfers of their wallet
+    mismatches: Arc<Mutex<Vec<TransferMismatch>>>,
+    /// Address of the liquidation router, whose swaps are recorded from its own events
+    liquidation_router: Address,
+    /// Address of the prize token sent to liquidate yield
+    prize_token: Address,
     phantom: PhantomData<(F, T, W)>,
 }
 
@@ -75,88 +299,859 @@ where
     P: Provider<T, AnyNetwork> + Clone,
     W: std::io::Write,
 {
-    pub fn new(provider: Arc<P>, config: Config) -> Self {
-        Self {
+    pub fn new(provider: Arc<P>, config: Config, cache: Arc<Cache>) -> eyre::Result<Self> {
+        let pairs =
+            PairResolver::new(provider.clone(), config.registry.clone(), &config.cache_dir)?;
+        let tokens = Arc::new(TokenResolver::new(
+            provider.clone(),
+            config.registry.clone(),
+            &config.cache_dir,
+        )?);
+        let prices = PriceResolver::new(
+            provider.clone(),
+            tokens.clone(),
+            config.prices,
+            config.registry.price_feeds.clone(),
+        )?;
+
+        Ok(Self {
             provider: provider.clone(),
-            chain_id: config.chain_id,
-            sender: config.sender,
+            registry: config.registry,
+            pairs: Arc::new(pairs),
+            tokens,
+            prices: Arc::new(prices),
+            cache,
+            wallets: config.senders.iter().map(|wallet| wallet.address).collect(),
+            match_on: config.match_on,
+            export_mode: config.export_mode,
+            net_transfers: config.net_transfers,
+            mismatches: Arc::new(Mutex::new(Vec::new())),
+            liquidation_router: config.liquidation_router,
+            prize_token: config.prize_token,
             phantom: PhantomData,
+        })
+    }
+
+    /// Decode a SwappedExactAmountOut event of the liquidation router or of a liquidation pair
+    /// into a liquidation of one of the wallets. Returns `None` if it does not belong to one of
+    /// the wallets, or if it is a pair event of a swap going through the liquidation router,
+    /// whose own event already records it.
+    fn decode_liquidation_event(&self, log: &Log) -> Result<Option<Liquidation>, BotError> {
+        let (sender, receiver, liquidation_pair, amount_in, amount_out) =
+            if log.topics().first() == Some(&SwappedExactAmountOut::SIGNATURE_HASH) {
+                let event = log
+                    .log_decode::<SwappedExactAmountOut>()
+                    .map_err(|error| {
+                        BotError::Decode(format!("SwappedExactAmountOut event: {}", error))
+                    })?
+                    .inner
+                    .data;
+
+                (
+                    event.sender,
+                    event.receiver,
+                    event.liquidationPair,
+                    event.amountIn,
+                    event.amountOut,
+                )
+            } else {
+                let event = log
+                    .log_decode::<LiquidationPair::SwappedExactAmountOut>()
+                    .map_err(|error| {
+                        BotError::Decode(format!("pair SwappedExactAmountOut event: {}", error))
+                    })?
+                    .inner
+                    .data;
+
+                // The router swaps the pair on behalf of the wallet, so the pair sees it as the
+                // sender.
+                if event.sender == self.liquidation_router {
+                    return Ok(None);
+                }
+
+                // Any contract can emit the pair event, so only the known pairs are trusted.
+                if !self.pairs.is_known_pair(log.address()) {
+                    return Ok(None);
+                }
+
+                (
+                    event.sender,
+                    event.receiver,
+                    log.address(),
+                    event.amountIn,
+                    event.amountOut,
+                )
+            };
+
+        Ok(self
+            .match_on
+            .wallet(&self.wallets, sender, receiver)
+            .map(|wallet| Liquidation {
+                wallet,
+                sender,
+                receiver,
+                liquidation_pair,
+                amount_in,
+                amount_out,
+            }))
+    }
+
+    /// Decode a liquidation router or liquidation pair SwappedExactAmountOut event into the trade
+    /// row of the liquidation, the prize token being sent for the token out of the pair. With
+    /// `net_transfers`, the rows are built from the net token flows of the transaction instead,
+    /// otherwise the amounts of the liquidations are checked against them.
+    /// With `MatchOn::Either`, a liquidation whose receiver is another wallet is recorded as the
+    /// prize tokens sent by the sender and the token out received by the receiver, each in a row
+    /// of its own wallet.
+    /// Returns no row if the liquidation does not belong to one of the wallets.
+    pub async fn decode_liquidation(&self, log: Log) -> Result<Vec<KoinlyData>, BotError> {
+        // Return early if the liquidation does not belong to one of the wallets, before fetching
+        // its block and receipt. The filter topics already exclude such logs, unless the provider
+        // ignores them.
+        let Some(liquidation) = self.decode_liquidation_event(&log)? else {
+            return Ok(Vec::new());
+        };
+
+        let tx_hash = log
+            .transaction_hash
+            .ok_or(BotError::MissingLogField("transaction hash"))?;
+        let block_number = log
+            .block_number
+            .ok_or(BotError::MissingLogField("block number"))?;
+        let wallet = liquidation.wallet;
+        let receiver = (self.match_on == MatchOn::Either
+            && liquidation.receiver != wallet
+            && self.wallets.contains(&liquidation.receiver))
+        .then_some(liquidation.receiver);
+
+        let date = self.get_date(block_number).await?;
+        let receipt = self.get_receipt(block_number, tx_hash).await?;
+        let fee = format_amount(self.get_transaction_fee(&receipt)?, 18)?;
+        let flows = TokenFlows::from_receipt(&receipt, wallet);
+
+        // The net flows are those of the whole transaction, so they are only recorded or checked
+        // once, by its first liquidation of the wallet.
+        let mut liquidations = Vec::new();
+
+        for receipt_log in receipt.inner.inner.logs() {
+            if self.is_recorded_liquidation(receipt_log) {
+                if let Some(liquidation) = self.decode_liquidation_event(receipt_log)? {
+                    if liquidation.wallet == wallet {
+                        liquidations.push((receipt_log.log_index, liquidation));
+                    }
+                }
+            }
+        }
+
+        let is_first = liquidations
+            .first()
+            .is_none_or(|(log_index, _)| *log_index == log.log_index);
+
+        // Liquidations whose tokens did not transit through the wallet, e.g. sent to another
+        // receiver, are recorded from the liquidation amounts.
+        let mut rows = if self.net_transfers && !is_first {
+            Vec::new()
+        } else if self.net_transfers && !flows.is_empty() {
+            let (sent, received) = flows.net_transfers();
+
+            self.decode_net_transfers(wallet, sent, received, date.clone(), fee, tx_hash)
+                .await?
+        } else {
+            if !self.net_transfers && is_first {
+                let liquidations = if liquidations.is_empty() {
+                    vec![liquidation.clone()]
+                } else {
+                    liquidations
+                        .into_iter()
+                        .map(|(_, liquidation)| liquidation)
+                        .collect()
+                };
+
+                self.check_transfers(&liquidations, &flows, &log).await?;
+            }
+
+            // The amount out is in the token sent by the pair, e.g. the vault shares rather than
+            // their underlying asset, as checked against the transfers.
+            let token_out = self
+                .get_pair_token_out(liquidation.liquidation_pair)
+                .await?;
+            let (amount_in, amount_in_symbol) = self
+                .format_token_amount(Some((self.prize_token, liquidation.amount_in)))
+                .await?;
+            let (amount_out, amount_out_symbol) = self
+                .format_token_amount(
+                    receiver
+                        .is_none()
+                        .then_some((token_out, liquidation.amount_out)),
+                )
+                .await?;
+
+            vec![KoinlyData {
+                date: date.clone(),
+                amount_in,
+                amount_in_symbol,
+                amount_out,
+                amount_out_symbol,
+                fee,
+                fee_symbol: self.registry.fee_token_symbol.clone(),
+                net_worth: String::new(),
+                net_worth_symbol: String::new(),
+                label: String::new(),
+                description: String::new(),
+                tx_hash: format!("0x{:064x}", tx_hash),
+                wallet,
+            }]
+        };
+
+        // The receiver did not send the transaction, so it paid no fee.
+        if let Some(receiver) = receiver {
+            let token_out = self
+                .get_pair_token_out(liquidation.liquidation_pair)
+                .await?;
+            let (amount_out, amount_out_symbol) = self
+                .format_token_amount(Some((token_out, liquidation.amount_out)))
+                .await?;
+
+            rows.push(KoinlyData {
+                date,
+                amount_in: String::new(),
+                amount_in_symbol: String::new(),
+                amount_out,
+                amount_out_symbol,
+                fee: String::new(),
+                fee_symbol: String::new(),
+                net_worth: String::new(),
+                net_worth_symbol: String::new(),
+                label: String::new(),
+                description: String::new(),
+                tx_hash: format!("0x{:064x}", tx_hash),
+                wallet: receiver,
+            });
+        }
+
+        Ok(rows)
+    }
+
+    /// Check the amounts of the liquidations of a wallet in a transaction against its net token
+    /// flows, the sender paying the prize tokens and the receiver getting the liquidated assets.
+    /// Mismatches are recorded for the report.
+    async fn check_transfers(
+        &self,
+        liquidations: &[Liquidation],
+        flows: &TokenFlows,
+        log: &Log,
+    ) -> Result<(), BotError> {
+        // Amounts expected to be sent, positive, or received, negative, for each token.
+        let mut expected: Vec<(Address, I256)> = Vec::new();
+
+        for liquidation in liquidations {
+            let mut amounts = Vec::new();
+
+            if liquidation.wallet == liquidation.sender {
+                amounts.push((self.prize_token, I256::from_raw(liquidation.amount_in)));
+            }
+
+            // The receiver gets the token out of the pair, which may be the vault shares
+            // rather than their underlying asset.
+            if liquidation.wallet == liquidation.receiver {
+                amounts.push((
+                    self.get_pair_token_out(liquidation.liquidation_pair)
+                        .await?,
+                    -I256::from_raw(liquidation.amount_out),
+                ));
+            }
+
+            for (token, amount) in amounts {
+                match expected.iter_mut().find(|(address, _)| *address == token) {
+                    Some((_, total)) => *total += amount,
+                    None => expected.push((token, amount)),
+                }
+            }
+        }
+
+        for (token, expected_sent, transferred_sent) in flows.unexpected_transfers(&expected) {
+            let token_info = self.get_token(token).await?;
+            let format_signed = |amount: I256| {
+                format_units(amount, token_info.decimals)
+                    .map_err(|error| BotError::Decode(format!("amount {}: {}", amount, error)))
+            };
+
+            // Amounts are reported in the direction expected by the liquidations, the transferred
+            // amount being negative if it went the other way.
+            let transferred = if expected_sent.is_negative() {
+                -transferred_sent
+            } else {
+                transferred_sent
+            };
+
+            let mismatch = TransferMismatch {
+                block_number: log.block_number.unwrap_or_default(),
+                log_index: log.log_index,
+                tx_hash: log.transaction_hash.unwrap_or_default(),
+                wallet: liquidations[0].wallet,
+                token,
+                symbol: token_info.symbol.clone(),
+                event_amount: format_signed(expected_sent.abs())?,
+                transferred_amount: format_signed(transferred)?,
+            };
+
+            log::warn!(
+                "Liquidations of {} in transaction {} transferred {} {} instead of {}",
+                mismatch.wallet,
+                mismatch.tx_hash,
+                mismatch.transferred_amount,
+                mismatch.symbol,
+                mismatch.event_amount
+            );
+
+            self.mismatches.lock().unwrap().push(mismatch);
+        }
+
+        Ok(())
+    }
+
+    /// Liquidations whose amounts differ from the net transfers of their wallet since the last
+    /// call, sorted by block and log index
+    pub fn take_mismatches(&self) -> Vec<TransferMismatch> {
+        let mut mismatches = std::mem::take(&mut *self.mismatches.lock().unwrap());
+        mismatches.sort_by_key(|mismatch| (mismatch.block_number, mismatch.log_index));
+        mismatches
+    }
+
+    /// Whether a log is a liquidation recorded by the bot, pair events of swaps going through the
+    /// liquidation router being recorded from the router event
+    fn is_recorded_liquidation(&self, log: &Log) -> bool {
+        match log.topics() {
+            [topic0, ..] if *topic0 == SwappedExactAmountOut::SIGNATURE_HASH => {
+                log.address() == self.liquidation_router
+            }
+            [topic0, sender, ..]
+                if *topic0 == LiquidationPair::SwappedExactAmountOut::SIGNATURE_HASH =>
+            {
+                *sender != self.liquidation_router.into_word()
+            }
+            _ => false,
+        }
+    }
+
+    /// Build the rows of the net token flows of a transaction, paired by `pair_net_transfers`:
+    /// trades of a single token sent for each token received, otherwise income rows of the
+    /// tokens received and cost rows of the tokens sent. The transaction fee is attached to the
+    /// first row.
+    async fn decode_net_transfers(
+        &self,
+        wallet: Address,
+        sent: TokenAmounts,
+        received: TokenAmounts,
+        date: String,
+        fee: String,
+        tx_hash: TxHash,
+    ) -> Result<Vec<KoinlyData>, BotError> {
+        let mut rows = Vec::new();
+
+        for (index, (sent, received)) in
+            pair_net_transfers(&sent, &received).into_iter().enumerate()
+        {
+            let (amount_in, amount_in_symbol) = self.format_token_amount(sent).await?;
+            let (amount_out, amount_out_symbol) = self.format_token_amount(received).await?;
+
+            let label = if amount_in.is_empty() {
+                INCOME_LABEL
+            } else if amount_out.is_empty() {
+                COST_LABEL
+            } else {
+                ""
+            };
+
+            let (fee, fee_symbol) = if index == 0 {
+                (fee.clone(), self.registry.fee_token_symbol.clone())
+            } else {
+                (String::new(), String::new())
+            };
+
+            rows.push(KoinlyData {
+                date: date.clone(),
+                amount_in,
+                amount_in_symbol,
+                amount_out,
+                amount_out_symbol,
+                fee,
+                fee_symbol,
+                net_worth: String::new(),
+                net_worth_symbol: String::new(),
+                label: label.to_string(),
+                description: String::new(),
+                tx_hash: format!("0x{:064x}", tx_hash),
+                wallet,
+            });
+        }
+
+        Ok(rows)
+    }
+
+    /// Decode a failed transaction sent to the liquidation router into a cost row of its fee.
+    /// Returns `None` if its receipt shows that it did not revert.
+    pub async fn decode_failed_transaction(
+        &self,
+        transaction: &FailedTransaction,
+    ) -> Result<Option<KoinlyData>, BotError> {
+        let receipt = self
+            .get_receipt(transaction.block_number, transaction.tx_hash)
+            .await?;
+
+        if receipt.inner.inner.status() {
+            return Ok(None);
+        }
+
+        Ok(Some(KoinlyData {
+            date: self.get_date(transaction.block_number).await?,
+            amount_in: format_amount(self.get_transaction_fee(&receipt)?, 18)?,
+            amount_in_symbol: self.registry.fee_token_symbol.clone(),
+            amount_out: String::new(),
+            amount_out_symbol: String::new(),
+            fee: String::new(),
+            fee_symbol: String::new(),
+            net_worth: String::new(),
+            net_worth_symbol: String::new(),
+            label: COST_LABEL.to_string(),
+            description: String::new(),
+            tx_hash: format!("0x{:064x}", transaction.tx_hash),
+            wallet: transaction.wallet,
+        }))
+    }
+
+    /// Decode a reward event into its recipient and amount of prize tokens. Prize claims pay
+    /// their fee to bots, and their prize to the winner in depositor mode.
+    fn decode_reward_event(&self, log: &Log) -> Result<(Address, U256), BotError> {
+        let decode_error = |event: &str, error: alloy::sol_types::Error| {
+            BotError::Decode(format!("{} event: {}", event, error))
+        };
+
+        match log.topics().first() {
+            Some(&ClaimedPrize::SIGNATURE_HASH) => {
+                let event = log
+                    .log_decode::<ClaimedPrize>()
+                    .map_err(|error| decode_error("ClaimedPrize", error))?
+                    .inner
+                    .data;
+
+                match self.export_mode {
+                    ExportMode::Bot => Ok((event.feeRecipient, U256::from(event.fee))),
+                    ExportMode::Depositor => Ok((event.winner, U256::from(event.payout))),
+                }
+            }
+            Some(&DrawStarted::SIGNATURE_HASH) => {
+                let event = log
+                    .log_decode::<DrawStarted>()
+                    .map_err(|error| decode_error("DrawStarted", error))?
+                    .inner
+                    .data;
+
+                Ok((event.recipient, event.reward))
+            }
+            Some(&DrawFinished::SIGNATURE_HASH) => {
+                let event = log
+                    .log_decode::<DrawFinished>()
+                    .map_err(|error| decode_error("DrawFinished", error))?
+                    .inner
+                    .data;
+
+                Ok((event.recipient, event.reward))
+            }
+            Some(&AuctionRewardAllocated::SIGNATURE_HASH) => {
+                let event = log
+                    .log_decode::<AuctionRewardAllocated>()
+                    .map_err(|error| decode_error("AuctionRewardAllocated", error))?
+                    .inner
+                    .data;
+
+                Ok((event.recipient, event.reward))
+            }
+            _ => Err(BotError::Decode("unknown reward event".to_string())),
         }
     }
 
-    /// Decode liquidation router SwappedExactAmountOut event
-    pub async fn decode_liquidation_router_event(&self, log: Log) -> Option<KoinlyData> {
-        let tx_hash = log.transaction_hash.unwrap();
+    /// Decode the reward events of a transaction (prize claiming fees and draw rewards) into reward
+    /// rows of the prize tokens paid to the wallets, one row per wallet. The transaction fee is
+    /// attached to the row of the wallet that sent the transaction, if any.
+    pub async fn decode_rewards(&self, logs: Vec<Log>) -> Result<Vec<KoinlyData>, BotError> {
+        let Some(first_log) = logs.first() else {
+            return Ok(Vec::new());
+        };
+
+        let tx_hash = first_log
+            .transaction_hash
+            .ok_or(BotError::MissingLogField("transaction hash"))?;
+        let block_number = first_log
+            .block_number
+            .ok_or(BotError::MissingLogField("block number"))?;
+
+        // Rewards paid to each wallet, in the order of the events.
+        let mut rewards: Vec<(Address, U256)> = Vec::new();
+
+        for log in &logs {
+            let (recipient, amount) = self.decode_reward_event(log)?;
+
+            if amount.is_zero() || !self.wallets.contains(&recipient) {
+                continue;
+            }
+
+            match rewards.iter_mut().find(|(wallet, _)| *wallet == recipient) {
+                Some((_, reward)) => *reward += amount,
+                None => rewards.push((recipient, amount)),
+            }
+        }
+
+        // Return early if nothing was paid to the wallets, before fetching the block and receipt.
+        if rewards.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let date = self.get_date(block_number).await?;
+        let receipt = self.get_receipt(block_number, tx_hash).await?;
+        let transaction_fee = format_amount(self.get_transaction_fee(&receipt)?, 18)?;
+        let prize_token = self.get_token(self.prize_token).await?;
+
+        rewards
+            .into_iter()
+            .map(|(wallet, reward)| {
+                // Rewards are often paid to a wallet that did not send the transaction, e.g. the
+                // recipient of an RNG auction completed by another bot, which paid no fee.
+                let (fee, fee_symbol) = if wallet == receipt.from {
+                    (
+                        transaction_fee.clone(),
+                        self.registry.fee_token_symbol.clone(),
+                    )
+                } else {
+                    (String::new(), String::new())
+                };
+
+                Ok(KoinlyData {
+                    date: date.clone(),
+                    amount_in: String::new(),
+                    amount_in_symbol: String::new(),
+                    amount_out: format_amount(reward, prize_token.decimals)?,
+                    amount_out_symbol: prize_token.symbol.clone(),
+                    fee,
+                    fee_symbol,
+                    net_worth: String::new(),
+                    net_worth_symbol: String::new(),
+                    label: match self.export_mode {
+                        ExportMode::Bot => REWARD_LABEL.to_string(),
+                        ExportMode::Depositor => INCOME_LABEL.to_string(),
+                    },
+                    description: String::new(),
+                    tx_hash: format!("0x{:064x}", tx_hash),
+                    wallet,
+                })
+            })
+            .collect()
+    }
+
+    /// Decode a Deposit or Withdraw event of an ERC-4626 vault into a trade between the underlying
+    /// asset and the vault shares. A withdrawal whose receiver is not the owner of the shares is
+    /// recorded as the shares sent by the owner and the assets received by the receiver instead.
+    /// Only the rows of the wallets are returned.
+    pub async fn decode_vault_event(&self, log: Log) -> Result<Vec<KoinlyData>, BotError> {
+        let tx_hash = log
+            .transaction_hash
+            .ok_or(BotError::MissingLogField("transaction hash"))?;
+        let block_number = log
+            .block_number
+            .ok_or(BotError::MissingLogField("block number"))?;
+        let vault = log.address();
+
+        // Wallet of each row with the amounts it sent and received, as (token, amount).
+        let legs = if log.topics().first() == Some(&Deposit::SIGNATURE_HASH) {
+            let event = log
+                .log_decode::<Deposit>()
+                .map_err(|error| BotError::Decode(format!("Deposit event: {}", error)))?
+                .inner
+                .data;
+
+            let asset = self.get_vault_asset(vault).await?;
+
+            vec![(
+                event.owner,
+                Some((asset, event.assets)),
+                Some((vault, event.shares)),
+            )]
+        } else {
+            let event = log
+                .log_decode::<Withdraw>()
+                .map_err(|error| BotError::Decode(format!("Withdraw event: {}", error)))?
+                .inner
+                .data;
+
+            let asset = self.get_vault_asset(vault).await?;
+
+            if event.receiver == event.owner {
+                vec![(
+                    event.owner,
+                    Some((vault, event.shares)),
+                    Some((asset, event.assets)),
+                )]
+            } else {
+                vec![
+                    (event.owner, Some((vault, event.shares)), None),
+                    (event.receiver, None, Some((asset, event.assets))),
+                ]
+            }
+        };
+
+        let legs: Vec<_> = legs
+            .into_iter()
+            .filter(|(wallet, _, _)| self.wallets.contains(wallet))
+            .collect();
+
+        if legs.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let date = self.get_date(block_number).await?;
+        let receipt = self.get_receipt(block_number, tx_hash).await?;
+        let mut rows = Vec::new();
+
+        for (wallet, sent, received) in legs {
+            // Deposits and withdrawals can be made on behalf of the wallet, which then paid no fee.
+            let (fee, fee_symbol) = if receipt.from == wallet {
+                (
+                    format_amount(self.get_transaction_fee(&receipt)?, 18)?,
+                    self.registry.fee_token_symbol.clone(),
+                )
+            } else {
+                (String::new(), String::new())
+            };
+
+            let (amount_in, amount_in_symbol) = self.format_token_amount(sent).await?;
+            let (amount_out, amount_out_symbol) = self.format_token_amount(received).await?;
+
+            rows.push(KoinlyData {
+                date: date.clone(),
+                amount_in,
+                amount_in_symbol,
+                amount_out,
+                amount_out_symbol,
+                fee,
+                fee_symbol,
+                net_worth: String::new(),
+                net_worth_symbol: String::new(),
+                label: String::new(),
+                description: String::new(),
+                tx_hash: format!("0x{:064x}", tx_hash),
+                wallet,
+            });
+        }
+
+        Ok(rows)
+    }
+
+    /// Format an amount of a token and its symbol, both empty if there is none
+    async fn format_token_amount(
+        &self,
+        amount: Option<(Address, U256)>,
+    ) -> Result<(String, String), BotError> {
+        let Some((token, amount)) = amount else {
+            return Ok((String::new(), String::new()));
+        };
+
+        let token = self.get_token(token).await?;
+
+        Ok((format_amount(amount, token.decimals)?, token.symbol))
+    }
+
+    /// Decode an exported item into its rows
+    pub async fn decode(&self, item: ExportItem) -> Result<Vec<KoinlyData>, BotError> {
+        let (block_number, _, _) = item.position();
+
+        let mut rows = match item {
+            ExportItem::Liquidation(log) | ExportItem::PairLiquidation(log) => {
+                self.decode_liquidation(log).await
+            }
+            ExportItem::Rewards(logs) => self.decode_rewards(logs).await,
+            ExportItem::Vault(log) => self.decode_vault_event(log).await,
+            ExportItem::FailedTransaction(transaction) => Ok(self
+                .decode_failed_transaction(&transaction)
+                .await?
+                .into_iter()
+                .collect()),
+        }?;
+
+        if let Some(block_number) = block_number.filter(|_| self.prices.is_enabled()) {
+            for row in rows.iter_mut() {
+                self.value_row(row, block_number).await?;
+            }
+        }
+
+        Ok(rows)
+    }
+
+    /// Fill the net worth of a row with the value of its received amount,
+    /// or of its sent amount if the received currency has no price
+    async fn value_row(&self, row: &mut KoinlyData, block_number: u64) -> Result<(), BotError> {
+        let timestamp = self.get_block_timestamp(block_number).await?;
+
+        for (amount, symbol) in [
+            (&row.amount_out, &row.amount_out_symbol),
+            (&row.amount_in, &row.amount_in_symbol),
+        ] {
+            if symbol.is_empty() {
+                continue;
+            }
+
+            if let Some(price) = self.prices.get_price(symbol, block_number, timestamp).await {
+                row.net_worth = price.value_of(amount)?;
+                row.net_worth_symbol = self.prices.currency().to_string();
+                row.description =
+                    format!("Net worth from the {} price of {}", price.source, symbol);
+
+                return Ok(());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Get the UTC date of a given block
+    async fn get_date(&self, block_number: u64) -> Result<String, BotError> {
+        let timestamp = self.get_block_timestamp(block_number).await?;
+
+        DateTime::from_timestamp(timestamp as i64, 0)
+            .map(|date_utc| date_utc.to_string())
+            .ok_or_else(|| BotError::Decode(format!("timestamp of block {}", block_number)))
+    }
+
+    /// Get the fee paid by a transaction, including the L1 data fee on OP stack chains
+    fn get_transaction_fee(&self, receipt: &AnyTransactionReceipt) -> Result<U256, BotError> {
+        let tx_hash = receipt.transaction_hash;
+
+        // OP stack chains charge an L1 data fee on top of the L2 execution fee.
+        let l1_fee = if self.registry.op_stack {
+            let optimism_fields: OptimismTransactionReceiptFields = receipt
+                .other
+                .clone()
+                .deserialize_into()
+                .map_err(|_| BotError::MissingL1Fee(tx_hash))?;
+
+            optimism_fields
+                .l1_block_info
+                .l1_fee
+                .ok_or(BotError::MissingL1Fee(tx_hash))?
+        } else {
+            0
+        };
+
+        Ok(U256::from(
+            receipt.gas_used * receipt.effective_gas_price + l1_fee,
+        ))
+    }
+
+    /// Get the timestamp of a given block, only fetching its header if it is not cached
+    async fn get_block_timestamp(&self, block_number: u64) -> Result<u64, BotError> {
+        if let Some(block) = self.cache.get_block(block_number) {
+            return Ok(block.timestamp);
+        }
 
         let block = self
             .provider
-            .get_block(
-                log.block_number.unwrap().into(),
-                BlockTransactionsKind::Full,
-            )
-            .await
-            .unwrap()
-            .unwrap();
+            .get_block(block_number.into(), BlockTransactionsKind::Hashes)
+            .await?
+            .ok_or(BotError::MissingBlock(block_number))?;
 
-        let date_utc = DateTime::from_timestamp(block.header.timestamp as i64, 0).unwrap();
+        self.cache.put_block(&CachedBlock {
+            number: block_number,
+            hash: block.header.hash,
+            timestamp: block.header.timestamp,
+        });
+
+        Ok(block.header.timestamp)
+    }
+
+    /// Get the receipt of a given transaction, fetching it if it is not cached
+    async fn get_receipt(
+        &self,
+        block_number: u64,
+        tx_hash: TxHash,
+    ) -> Result<AnyTransactionReceipt, BotError> {
+        if let Some(receipt) = self.cache.get_receipt(block_number, tx_hash) {
+            return Ok(receipt);
+        }
 
         let receipt = AnyTransactionReceipt::from(
             self.provider
                 .get_transaction_receipt(tx_hash)
-                .await
-                .unwrap()
-                .unwrap(),
+                .await?
+                .ok_or(BotError::MissingReceipt(tx_hash))?,
         );
 
-        let optimism_fields: OptimismTransactionReceiptFields =
-            receipt.other.clone().deserialize_into().unwrap();
+        self.cache.put_receipt(block_number, &receipt);
 
-        let mut event_data: Option<KoinlyData> = None;
+        Ok(receipt)
+    }
 
-        if let Ok(decoded_log) = log.log_decode::<SwappedExactAmountOut>() {
-            let event = decoded_log.inner.data;
+    /// Liquidation pairs of the registry and discovered so far
+    pub fn known_pairs(&self) -> Vec<Address> {
+        self.pairs.known_pair_addresses()
+    }
 
-            // Return early if the transaction is not from the sender.
-            if event.sender != self.sender {
-                return None;
-            }
+    /// Prize vaults liquidated by the liquidation pairs of the registry and discovered so far
+    pub async fn prize_vaults(&self) -> Vec<Address> {
+        let mut vaults: Vec<_> = self
+            .pairs
+            .known_pairs()
+            .await
+            .into_iter()
+            .map(|pair| pair.source)
+            .collect();
 
-            let amount_out_asset_address =
-                get_underlying_asset_address(self.chain_id, event.liquidationPair);
+        vaults.sort();
+        vaults.dedup();
 
-            event_data = Some(KoinlyData {
-                date: date_utc.to_string(),
-                amount_in: format_units(
-                    event.amountIn,
-                    get_asset_decimals(self.chain_id, *POOL_OPTIMISM_ADDRESS),
-                )
-                .unwrap(),
-                amount_in_symbol: get_asset_symbol(self.chain_id, *POOL_OPTIMISM_ADDRESS)
-                    .to_string(),
-                amount_out: format_units(
-                    event.amountOut,
-                    get_asset_decimals(self.chain_id, amount_out_asset_address),
-                )
-                .unwrap(),
-                amount_out_symbol: get_asset_symbol(self.chain_id, amount_out_asset_address)
-                    .to_string(),
-                fee: format_units(
-                    receipt.gas_used * receipt.effective_gas_price
-                        + optimism_fields.l1_block_info.l1_fee.unwrap(),
-                    18,
-                )
-                .unwrap(),
-                fee_symbol: "ETH".to_string(),
-                tx_hash: format!("0x{:064x}", tx_hash),
-            });
-        }
+        vaults
+    }
+
+    /// Get the underlying asset of a given vault
+    async fn get_vault_asset(&self, vault: Address) -> Result<Address, BotError> {
+        self.pairs
+            .get_vault_asset_address(vault)
+            .await
+            .map_err(|error| BotError::UnknownVault {
+                vault,
+                reason: error.to_string(),
+            })
+    }
+
+    /// Get the token out of a given liquidation pair
+    async fn get_pair_token_out(&self, liquidation_pair: Address) -> Result<Address, BotError> {
+        self.pairs
+            .get_token_out_address(liquidation_pair)
+            .await
+            .map_err(|error| BotError::UnknownPair {
+                pair: liquidation_pair,
+                reason: error.to_string(),
+            })
+    }
 
-        event_data
+    /// Get the symbol and decimals of a given token
+    async fn get_token(&self, token: Address) -> Result<TokenInfo, BotError> {
+        self.tokens
+            .get_token(token)
+            .await
+            .map_err(|error| BotError::UnknownToken {
+                token,
+                reason: error.to_string(),
+            })
     }
 
-    /// Write liquidation data into the CSV file
-    pub async fn write_to_koinly_csv(&self, wtr: &mut csv::Writer<W>, data: KoinlyData) {
-        let _ = wtr.serialize(KoinlyData {
+    /// Write a row into the CSV file, followed by its value when prices are configured
+    /// and by the wallet name in combined files
+    pub async fn write_to_koinly_csv(
+        &self,
+        wtr: &mut csv::Writer<W>,
+        data: KoinlyData,
+        wallet: Option<&str>,
+    ) -> eyre::Result<()> {
+        let data = KoinlyData {
             date: data.date.to_string(),
             amount_in: data.amount_in,
             amount_in_symbol: data.amount_in_symbol,
@@ -164,9 +1159,320 @@ where
             amount_out_symbol: data.amount_out_symbol,
             fee: data.fee,
             fee_symbol: data.fee_symbol,
+            net_worth: data.net_worth,
+            net_worth_symbol: data.net_worth_symbol,
+            label: data.label,
+            description: data.description,
             tx_hash: data.tx_hash,
-        });
+            wallet: data.wallet,
+        };
+
+        let net_worth = NetWorthColumns {
+            net_worth: &data.net_worth,
+            net_worth_symbol: &data.net_worth_symbol,
+            description: &data.description,
+        };
+
+        match (self.prices.is_enabled(), wallet) {
+            (true, Some(wallet)) => wtr.serialize((&data, net_worth, WalletColumn { wallet }))?,
+            (true, None) => wtr.serialize((&data, net_worth))?,
+            (false, Some(wallet)) => wtr.serialize((&data, WalletColumn { wallet }))?,
+            (false, None) => wtr.serialize(&data)?,
+        }
 
         log_info_cyan!("Inserted liquidation data into CSV!");
+
+        Ok(())
+    }
+}
+
+/// Format a token amount with its decimals
+fn format_amount(amount: U256, decimals: u8) -> Result<String, BotError> {
+    format_units(amount, decimals)
+        .map_err(|error| BotError::Decode(format!("amount {}: {}", amount, error)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        cli::ConfigArgs,
+        mock_rpc::{self, MockChain, MockRpc, PAIR, PRIZE_TOKEN},
+        transfers::Transfer,
+    };
+
+    use alloy::{
+        primitives::{address, LogData},
+        providers::ProviderBuilder,
+    };
+    use std::path::Path;
+
+    const WALLET: Address = address!("00000000000000000000000000000000000000aa");
+    const PRIZE_POOL: Address = address!("00000000000000000000000000000000000000e1");
+    const DRAW_MANAGER: Address = address!("00000000000000000000000000000000000000e2");
+    const ROUTER: Address = address!("00000000000000000000000000000000000000e3");
+
+    type TestBot = Bot<Filler, Http<Client>, RootProvider<Http<Client>, AnyNetwork>, Vec<u8>>;
+
+    /// Log of a transaction with a given event signature, the wallet being its first indexed topic
+    fn log(address: Address, signature: B256, tx_hash: u8, log_index: u64) -> Log {
+        Log {
+            inner: alloy::primitives::Log {
+                address,
+                data: LogData::new_unchecked(
+                    vec![signature, WALLET.into_word()],
+                    Default::default(),
+                ),
+            },
+            block_number: Some(1),
+            transaction_hash: Some(TxHash::with_last_byte(tx_hash)),
+            transaction_index: Some(u64::from(tx_hash)),
+            log_index: Some(log_index),
+            ..Default::default()
+        }
+    }
+
+    /// Bot recording the liquidations of the wallet on a mock chain, matched on either their
+    /// sender or receiver, caching into `dir`
+    fn bot(rpc: &MockRpc, dir: &Path) -> TestBot {
+        let config = Config::from_args(ConfigArgs {
+            http_rpc: Some(rpc.url.to_string()),
+            chain_id: Some("10".to_string()),
+            sender: Some(WALLET.to_string()),
+            match_on: Some("either".to_string()),
+            start_timestamp: Some(MockChain::timestamp(0).to_string()),
+            end_timestamp: Some(MockChain::timestamp(10).to_string()),
+            registry: Some(mock_rpc::write_registry(dir).display().to_string()),
+            cache_dir: Some(dir.join("cache").display().to_string()),
+            ..Default::default()
+        })
+        .unwrap();
+        let provider = ProviderBuilder::new()
+            .network::<AnyNetwork>()
+            .on_http(rpc.url.clone());
+        let cache = Arc::new(Cache::new(&config.cache_dir, 10));
+
+        Bot::new(Arc::new(provider), config, cache).unwrap()
+    }
+
+    /// Log of a transaction of the mock chain, as returned by `eth_getLogs`
+    fn chain_log(
+        address: Address,
+        data: LogData,
+        block_number: u64,
+        tx_hash: TxHash,
+        log_index: u64,
+    ) -> Log {
+        Log {
+            inner: alloy::primitives::Log { address, data },
+            block_number: Some(block_number),
+            transaction_hash: Some(tx_hash),
+            log_index: Some(log_index),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn skips_pair_events_of_router_swaps() {
+        let amount_in = U256::from(10);
+        let amount_out = U256::from(1000);
+        let prize_transfer = Transfer {
+            from: WALLET,
+            to: PAIR,
+            value: amount_in,
+        }
+        .encode_log_data();
+        let shares_transfer = Transfer {
+            from: PAIR,
+            to: WALLET,
+            value: amount_out,
+        }
+        .encode_log_data();
+        let pair_event = |sender| {
+            LiquidationPair::SwappedExactAmountOut {
+                sender,
+                receiver: WALLET,
+                amountOut: amount_out,
+                amountInMax: amount_in,
+                amountIn: amount_in,
+                flashSwapData: Default::default(),
+            }
+            .encode_log_data()
+        };
+        let router_event = SwappedExactAmountOut {
+            liquidationPair: PAIR,
+            sender: WALLET,
+            receiver: WALLET,
+            amountOut: amount_out,
+            amountInMax: amount_in,
+            amountIn: amount_in,
+            deadline: U256::ZERO,
+        }
+        .encode_log_data();
+
+        // The wallet swaps the pair through the router in block 1, then directly in block 2. The
+        // wallet is the receiver of both pair events, so only the router as their sender tells
+        // them apart.
+        let mut chain = MockChain::new(10).with_pair();
+        let router_tx = chain.transaction(
+            1,
+            WALLET,
+            vec![
+                (PRIZE_TOKEN, prize_transfer.clone()),
+                (mock_rpc::VAULT, shares_transfer.clone()),
+                (PAIR, pair_event(mock_rpc::ROUTER)),
+                (mock_rpc::ROUTER, router_event.clone()),
+            ],
+        );
+        let direct_tx = chain.transaction(
+            2,
+            WALLET,
+            vec![
+                (PRIZE_TOKEN, prize_transfer),
+                (mock_rpc::VAULT, shares_transfer),
+                (PAIR, pair_event(WALLET)),
+            ],
+        );
+
+        let rpc = chain.start().await;
+        let dir = std::env::temp_dir().join(format!("bot-{}", rand::random::<u64>()));
+        let bot = bot(&rpc, &dir);
+
+        let router_pair_rows = bot
+            .decode_liquidation(chain_log(
+                PAIR,
+                pair_event(mock_rpc::ROUTER),
+                1,
+                router_tx,
+                2,
+            ))
+            .await
+            .unwrap();
+        let router_rows = bot
+            .decode_liquidation(chain_log(mock_rpc::ROUTER, router_event, 1, router_tx, 3))
+            .await
+            .unwrap();
+        let direct_rows = bot
+            .decode_liquidation(chain_log(PAIR, pair_event(WALLET), 2, direct_tx, 2))
+            .await
+            .unwrap();
+
+        assert!(router_pair_rows.is_empty());
+        assert_eq!(router_rows.len(), 1);
+        assert_eq!(direct_rows.len(), 1);
+
+        for row in router_rows.iter().chain(&direct_rows) {
+            assert_eq!(
+                (row.amount_in.as_str(), row.amount_in_symbol.as_str()),
+                ("0.000000000000000010", "POOL")
+            );
+        }
+
+        // Both swaps transferred the amounts of their events.
+        assert!(bot.take_mismatches().is_empty());
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[tokio::test]
+    async fn records_liquidations_in_the_token_out() {
+        let amount_in = U256::from(10);
+        let amount_out = U256::from(1000);
+        let pair_event = LiquidationPair::SwappedExactAmountOut {
+            sender: WALLET,
+            receiver: WALLET,
+            amountOut: amount_out,
+            amountInMax: amount_in,
+            amountIn: amount_in,
+            flashSwapData: Default::default(),
+        }
+        .encode_log_data();
+        let transfer =
+            |token, from, to, value| (token, Transfer { from, to, value }.encode_log_data());
+
+        // The pair sends the przDAI shares of its vault, whose underlying asset is DAI, first in
+        // full, then short of one share.
+        let mut chain = MockChain::new(10).with_pair();
+        let tx_hashes: Vec<_> = [amount_out, amount_out - U256::from(1)]
+            .into_iter()
+            .enumerate()
+            .map(|(index, transferred)| {
+                chain.transaction(
+                    index as u64 + 1,
+                    WALLET,
+                    vec![
+                        transfer(PRIZE_TOKEN, WALLET, PAIR, amount_in),
+                        transfer(mock_rpc::VAULT, PAIR, WALLET, transferred),
+                        (PAIR, pair_event.clone()),
+                    ],
+                )
+            })
+            .collect();
+
+        let rpc = chain.start().await;
+        let dir = std::env::temp_dir().join(format!("bot-{}", rand::random::<u64>()));
+        let bot = bot(&rpc, &dir);
+
+        for (index, tx_hash) in tx_hashes.iter().enumerate() {
+            let rows = bot
+                .decode_liquidation(chain_log(
+                    PAIR,
+                    pair_event.clone(),
+                    index as u64 + 1,
+                    *tx_hash,
+                    2,
+                ))
+                .await
+                .unwrap();
+
+            assert_eq!(
+                (
+                    rows[0].amount_out.as_str(),
+                    rows[0].amount_out_symbol.as_str()
+                ),
+                ("0.000000000000001000", "przDAI")
+            );
+        }
+
+        // Only the shares missing from the second transaction are reported, in the same token
+        // as the rows.
+        let mismatches = bot.take_mismatches();
+
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(mismatches[0].tx_hash, tx_hashes[1]);
+        assert_eq!(mismatches[0].symbol, "przDAI");
+        assert_eq!(mismatches[0].transferred_amount, "0.000000000000000999");
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn groups_the_reward_logs_of_each_transaction() {
+        let items = ExportItem::from_logs(vec![
+            log(PRIZE_POOL, ClaimedPrize::SIGNATURE_HASH, 1, 0),
+            log(ROUTER, SwappedExactAmountOut::SIGNATURE_HASH, 1, 1),
+            log(DRAW_MANAGER, DrawFinished::SIGNATURE_HASH, 1, 2),
+            log(PRIZE_POOL, ClaimedPrize::SIGNATURE_HASH, 2, 3),
+            log(PRIZE_POOL, ClaimedPrize::SIGNATURE_HASH, 1, 4),
+        ]);
+
+        let log_indexes: Vec<_> = items
+            .iter()
+            .map(|item| match item {
+                ExportItem::Rewards(logs) => logs.iter().map(|log| log.log_index).collect(),
+                item => vec![item.position().2],
+            })
+            .collect();
+
+        assert_eq!(
+            log_indexes,
+            vec![
+                vec![Some(0), Some(2), Some(4)],
+                vec![Some(1)],
+                vec![Some(3)]
+            ]
+        );
+        assert!(matches!(items[1], ExportItem::Liquidation(_)));
     }
 }
@@ -0,0 +1,343 @@
+use crate::helpers::write_json_atomically;
+
+use alloy::{
+    primitives::{keccak256, TxHash, B256},
+    rpc::types::{AnyTransactionReceipt, Filter, Log},
+};
+use clap::ValueEnum;
+use eyre::{eyre, Result};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Kind of data cached on disk
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum CacheKind {
+    Blocks,
+    Receipts,
+    Logs,
+    /// Liquidation pairs resolved on-chain
+    Pairs,
+    /// Tokens resolved on-chain
+    Tokens,
+}
+
+impl CacheKind {
+    pub const ALL: [CacheKind; 5] = [
+        CacheKind::Blocks,
+        CacheKind::Receipts,
+        CacheKind::Logs,
+        CacheKind::Pairs,
+        CacheKind::Tokens,
+    ];
+
+    /// Name of the directory, or of the file for data resolved on-chain, under the directory of the chain
+    fn dir_name(&self) -> &'static str {
+        match self {
+            CacheKind::Blocks => "blocks",
+            CacheKind::Receipts => "receipts",
+            CacheKind::Logs => "logs",
+            CacheKind::Pairs => "liquidation-pairs.json",
+            CacheKind::Tokens => "tokens.json",
+        }
+    }
+}
+
+/// First and last blocks covered by a cache entry
+type BlockRange = (u64, u64);
+
+/// Block header fields used by the exports
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CachedBlock {
+    pub number: u64,
+    pub hash: B256,
+    pub timestamp: u64,
+}
+
+/// Number of entries, size and block range of a kind of cached data
+#[derive(Debug, Default)]
+pub struct CacheStats {
+    pub entries: u64,
+    pub bytes: u64,
+    pub first_block: Option<u64>,
+    pub last_block: Option<u64>,
+}
+
+/// On-disk cache of blocks, receipts, logs, pairs and tokens of a chain, stored as JSON files under
+/// `<cache_dir>/<chain_id>/`:
+/// - `blocks/<block>.json`
+/// - `receipts/<block>/<tx_hash>.json`
+/// - `logs/<filter>/<from_block>-<to_block>.json`
+/// - `liquidation-pairs.json` and `tokens.json`, written by the pair and token resolvers
+///
+/// Data of blocks after the safe block is never written, since it could still be reorged.
+/// Pairs and tokens don't belong to a block range, so they are kept when pruning and removed
+/// by any invalidation, e.g. a reorg could have dropped a discovered pair.
+pub struct Cache {
+    /// Directory of the chain
+    dir: PathBuf,
+    /// Last block whose data can be written to the cache
+    safe_block: u64,
+}
+
+impl Cache {
+    pub fn new(cache_dir: &Path, chain_id: u64) -> Self {
+        Self {
+            dir: cache_dir.join(chain_id.to_string()),
+            safe_block: 0,
+        }
+    }
+
+    /// Path of the file of the pairs or tokens of a chain resolved on-chain
+    pub fn resolved_path(cache_dir: &Path, chain_id: u64, kind: CacheKind) -> PathBuf {
+        Self::new(cache_dir, chain_id).dir.join(kind.dir_name())
+    }
+
+    /// Allow writing the data of blocks up to `safe_block`
+    pub fn with_safe_block(mut self, safe_block: u64) -> Self {
+        self.safe_block = safe_block;
+        self
+    }
+
+    pub fn get_block(&self, block_number: u64) -> Option<CachedBlock> {
+        self.read(&self.block_path(block_number))
+    }
+
+    pub fn put_block(&self, block: &CachedBlock) {
+        if block.number <= self.safe_block {
+            self.write(&self.block_path(block.number), block);
+        }
+    }
+
+    pub fn get_receipt(&self, block_number: u64, tx_hash: TxHash) -> Option<AnyTransactionReceipt> {
+        self.read(&self.receipt_path(block_number, tx_hash))
+    }
+
+    pub fn put_receipt(&self, block_number: u64, receipt: &AnyTransactionReceipt) {
+        if block_number <= self.safe_block {
+            self.write(
+                &self.receipt_path(block_number, receipt.transaction_hash),
+                receipt,
+            );
+        }
+    }
+
+    pub fn get_logs(&self, filter: &Filter, from_block: u64, to_block: u64) -> Option<Vec<Log>> {
+        self.read(&self.logs_path(filter, from_block, to_block))
+    }
+
+    pub fn put_logs(&self, filter: &Filter, from_block: u64, to_block: u64, logs: &[Log]) {
+        if to_block <= self.safe_block {
+            self.write(&self.logs_path(filter, from_block, to_block), logs);
+        }
+    }
+
+    /// Number of entries, size and block range of the cached data of a given kind
+    pub fn stats(&self, kind: CacheKind) -> Result<CacheStats> {
+        let mut stats = CacheStats::default();
+
+        for (path, range) in self.entries(kind)? {
+            stats.entries += 1;
+            stats.bytes += fs::metadata(&path)?.len();
+
+            let Some((first_block, last_block)) = range else {
+                continue;
+            };
+
+            stats.first_block = Some(
+                stats
+                    .first_block
+                    .map_or(first_block, |block| block.min(first_block)),
+            );
+            stats.last_block = Some(
+                stats
+                    .last_block
+                    .map_or(last_block, |block| block.max(last_block)),
+            );
+        }
+
+        Ok(stats)
+    }
+
+    /// Remove the entries of a given kind whose blocks are all before `block_number`
+    pub fn prune(&self, kind: CacheKind, block_number: u64) -> Result<u64> {
+        self.remove_entries(kind, |range| {
+            range.is_some_and(|(_, last_block)| last_block < block_number)
+        })
+    }
+
+    /// Remove the entries of a given kind that include blocks at or after `block_number`
+    pub fn invalidate(&self, kind: CacheKind, block_number: u64) -> Result<u64> {
+        self.remove_entries(kind, |range| {
+            range.is_none_or(|(_, last_block)| last_block >= block_number)
+        })
+    }
+
+    fn remove_entries(
+        &self,
+        kind: CacheKind,
+        predicate: impl Fn(Option<BlockRange>) -> bool,
+    ) -> Result<u64> {
+        let mut removed = 0;
+
+        for (path, range) in self.entries(kind)? {
+            if predicate(range) {
+                fs::remove_file(&path)
+                    .map_err(|error| eyre!("Failed to remove {}: {}", path.display(), error))?;
+                removed += 1;
+
+                // Remove the directory of the block or filter once empty, fails otherwise.
+                if let Some(parent) = path.parent() {
+                    fs::remove_dir(parent).ok();
+                }
+            }
+        }
+
+        Ok(removed)
+    }
+
+    /// Files of a given kind with the first and last blocks they cover, if any
+    fn entries(&self, kind: CacheKind) -> Result<Vec<(PathBuf, Option<BlockRange>)>> {
+        let root = self.dir.join(kind.dir_name());
+        let mut entries = Vec::new();
+
+        // Receipts and logs are grouped in one directory per block and per filter respectively.
+        let dirs = match kind {
+            CacheKind::Blocks => vec![root],
+            CacheKind::Receipts | CacheKind::Logs => list_dir(&root)?,
+            CacheKind::Pairs | CacheKind::Tokens => {
+                return Ok(root.is_file().then_some((root, None)).into_iter().collect())
+            }
+        };
+
+        for dir in dirs {
+            for path in list_dir(&dir)? {
+                let Some(stem) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                    continue;
+                };
+
+                let range = match kind {
+                    CacheKind::Blocks => stem.parse().ok().map(|block| (block, block)),
+                    CacheKind::Receipts => dir
+                        .file_name()
+                        .and_then(|name| name.to_str()?.parse().ok())
+                        .map(|block| (block, block)),
+                    CacheKind::Logs => stem
+                        .split_once('-')
+                        .and_then(|(from, to)| Some((from.parse().ok()?, to.parse().ok()?))),
+                    CacheKind::Pairs | CacheKind::Tokens => None,
+                };
+
+                if range.is_some() {
+                    entries.push((path, range));
+                }
+            }
+        }
+
+        Ok(entries)
+    }
+
+    fn block_path(&self, block_number: u64) -> PathBuf {
+        self.dir
+            .join(CacheKind::Blocks.dir_name())
+            .join(format!("{}.json", block_number))
+    }
+
+    fn receipt_path(&self, block_number: u64, tx_hash: TxHash) -> PathBuf {
+        self.dir
+            .join(CacheKind::Receipts.dir_name())
+            .join(block_number.to_string())
+            .join(format!("{:x}.json", tx_hash))
+    }
+
+    fn logs_path(&self, filter: &Filter, from_block: u64, to_block: u64) -> PathBuf {
+        self.dir
+            .join(CacheKind::Logs.dir_name())
+            .join(filter_key(filter))
+            .join(format!("{}-{}.json", from_block, to_block))
+    }
+
+    /// Read an entry, entries that are missing or fail to be parsed are cache misses
+    fn read<V: DeserializeOwned>(&self, path: &Path) -> Option<V> {
+        let content = fs::read(path).ok()?;
+
+        match serde_json::from_slice(&content) {
+            Ok(value) => Some(value),
+            Err(error) => {
+                log::warn!("Ignoring invalid cache entry {}: {}", path.display(), error);
+                None
+            }
+        }
+    }
+
+    /// Write an entry, failures are logged since the cache is only an optimization
+    fn write<V: Serialize + ?Sized>(&self, path: &Path, value: &V) {
+        if let Err(error) = write_json_atomically(path, value) {
+            log::warn!("Failed to write cache entry {}: {}", path.display(), error);
+        }
+    }
+}
+
+fn list_dir(dir: &Path) -> Result<Vec<PathBuf>> {
+    match fs::read_dir(dir) {
+        Ok(entries) => Ok(entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .collect()),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(error) => Err(eyre!("Failed to read {}: {}", dir.display(), error)),
+    }
+}
+
+/// Identify a filter by its addresses and topics, independently of their order and block range
+fn filter_key(filter: &Filter) -> String {
+    let mut addresses: Vec<_> = filter.address.iter().collect();
+    addresses.sort();
+
+    let mut bytes: Vec<u8> = addresses
+        .iter()
+        .flat_map(|address| address.to_vec())
+        .collect();
+
+    for topic in &filter.topics {
+        let mut values: Vec<_> = topic.iter().collect();
+        values.sort();
+
+        // Separate the topics so that values cannot be attributed to the wrong position.
+        bytes.push(0xff);
+        bytes.extend(values.iter().flat_map(|value| value.to_vec()));
+    }
+
+    format!("{:x}", keccak256(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prunes_and_invalidates_resolved_data() {
+        let cache_dir = std::env::temp_dir().join(format!("cache-{}", rand::random::<u64>()));
+        let cache = Cache::new(&cache_dir, 1).with_safe_block(100);
+        let pairs_path = Cache::resolved_path(&cache_dir, 1, CacheKind::Pairs);
+
+        for number in [10, 20] {
+            cache.put_block(&CachedBlock {
+                number,
+                hash: B256::ZERO,
+                timestamp: number,
+            });
+        }
+        write_json_atomically(&pairs_path, &serde_json::json!({})).unwrap();
+
+        assert_eq!(cache.prune(CacheKind::Blocks, 15).unwrap(), 1);
+        assert_eq!(cache.prune(CacheKind::Pairs, 15).unwrap(), 0);
+        assert_eq!(cache.stats(CacheKind::Pairs).unwrap().entries, 1);
+
+        assert_eq!(cache.invalidate(CacheKind::Blocks, 30).unwrap(), 0);
+        assert_eq!(cache.invalidate(CacheKind::Pairs, 30).unwrap(), 1);
+        assert!(!pairs_path.exists());
+        assert_eq!(cache.stats(CacheKind::Blocks).unwrap().last_block, Some(20));
+
+        fs::remove_dir_all(cache_dir).ok();
+    }
+}
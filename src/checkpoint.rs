@@ -0,0 +1,209 @@
+use crate::{
+    append::WrittenRows,
+    errors::LogFailure,
+    helpers::write_json_atomically,
+    prices::PriceConfig,
+    transfers::TransferMismatch,
+    wallets::{ExportMode, MatchOn, Wallet, WalletOutput},
+};
+
+use alloy::primitives::Address;
+use eyre::{eyre, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::path::{Path, PathBuf};
+
+/// Parameters of an export that determine the rows of its CSV files.
+/// A checkpoint is only resumed by an export with the same parameters, except for its last block.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExportParams {
+    pub chain_id: u64,
+    pub senders: Vec<Wallet>,
+    pub wallet_output: WalletOutput,
+    pub match_on: MatchOn,
+    pub liquidation_router: Address,
+    pub prize_token: Address,
+    pub record_reverted: bool,
+    pub net_transfers: bool,
+    pub prize_pool: Option<Address>,
+    pub draw_manager: Option<Address>,
+    pub rng_relay_auction: Option<Address>,
+    pub export_mode: ExportMode,
+    pub vaults: Vec<Address>,
+    pub prices: PriceConfig,
+    pub from_block: u64,
+    pub to_block: u64,
+}
+
+impl ExportParams {
+    /// Whether both exports write the rows of the same wallets, whatever their block range
+    pub fn same_wallets(&self, other: &ExportParams) -> bool {
+        self.chain_id == other.chain_id
+            && self.senders == other.senders
+            && self.wallet_output == other.wallet_output
+            && self.match_on == other.match_on
+            && self.liquidation_router == other.liquidation_router
+            && self.prize_token == other.prize_token
+            && self.record_reverted == other.record_reverted
+            && self.net_transfers == other.net_transfers
+            && self.prize_pool == other.prize_pool
+            && self.draw_manager == other.draw_manager
+            && self.rng_relay_auction == other.rng_relay_auction
+            && self.export_mode == other.export_mode
+            && self.vaults == other.vaults
+            && self.prices == other.prices
+    }
+}
+
+/// Progress of an export, saved after each chunk of blocks so that an interrupted export
+/// can continue where it left off
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub params: ExportParams,
+    /// First block that has not been processed yet
+    pub next_block: u64,
+    /// Size of each CSV file once the rows of the processed blocks were written
+    pub bytes_written: BTreeMap<String, u64>,
+    /// Rows written to each CSV file at its last date
+    pub written_rows: BTreeMap<String, WrittenRows>,
+    /// Logs of the processed blocks that failed to be decoded
+    pub failures: Vec<LogFailure>,
+    /// Liquidations of the processed blocks whose amounts differ from their transfers
+    pub mismatches: Vec<TransferMismatch>,
+}
+
+impl Checkpoint {
+    /// Path of the checkpoint of an export to a given CSV file, or to the files split from it
+    pub fn path(output_path: &str) -> PathBuf {
+        PathBuf::from(format!(
+            "{}.checkpoint.json",
+            output_path.trim_end_matches(".csv")
+        ))
+    }
+
+    /// Load the checkpoint of an interrupted export, if any
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(error) => {
+                return Err(eyre!(
+                    "Failed to read checkpoint {}: {}",
+                    path.display(),
+                    error
+                ))
+            }
+        };
+
+        serde_json::from_str(&content)
+            .map(Some)
+            .map_err(|error| eyre!("Failed to parse checkpoint {}: {}", path.display(), error))
+    }
+
+    /// Whether an export with the given parameters can continue from the checkpoint. Its last
+    /// block may differ, e.g. when the end timestamp is not reached yet or resolves to another
+    /// block after a reorg, as long as the checkpoint does not go past it.
+    pub fn resumes(&self, params: &ExportParams) -> bool {
+        self.params.same_wallets(params)
+            && self.params.from_block == params.from_block
+            && self.next_block <= params.to_block + 1
+    }
+
+    /// Open the CSV file and drop the rows written after the checkpoint
+    pub fn truncate_csv(&self, output_path: &str) -> Result<File> {
+        let bytes_written = *self.bytes_written.get(output_path).ok_or_else(|| {
+            eyre!(
+                "CSV file {} is missing from its checkpoint, run the export with --restart",
+                output_path
+            )
+        })?;
+
+        let file = OpenOptions::new().write(true).open(output_path)?;
+
+        if file.metadata()?.len() < bytes_written {
+            return Err(eyre!(
+                "CSV file {} is shorter than its checkpoint, run the export with --restart",
+                output_path
+            ));
+        }
+
+        file.set_len(bytes_written)?;
+
+        Ok(file)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        write_json_atomically(path, self)
+            .map_err(|error| eyre!("Failed to write checkpoint {}: {}", path.display(), error))
+    }
+
+    /// Remove the checkpoint once the export is complete
+    pub fn remove(path: &Path) -> Result<()> {
+        match std::fs::remove_file(path) {
+            Err(error) if error.kind() != std::io::ErrorKind::NotFound => Err(eyre!(
+                "Failed to remove checkpoint {}: {}",
+                path.display(),
+                error
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use alloy::primitives::address;
+
+    fn params(from_block: u64, to_block: u64) -> ExportParams {
+        ExportParams {
+            chain_id: 10,
+            senders: vec![Wallet {
+                address: address!("00000000000000000000000000000000000000aa"),
+                label: None,
+            }],
+            wallet_output: WalletOutput::default(),
+            match_on: MatchOn::default(),
+            liquidation_router: Address::ZERO,
+            prize_token: Address::ZERO,
+            record_reverted: false,
+            net_transfers: false,
+            prize_pool: None,
+            draw_manager: None,
+            rng_relay_auction: None,
+            export_mode: ExportMode::default(),
+            vaults: Vec::new(),
+            prices: PriceConfig::default(),
+            from_block,
+            to_block,
+        }
+    }
+
+    fn checkpoint(params: ExportParams, next_block: u64) -> Checkpoint {
+        Checkpoint {
+            params,
+            next_block,
+            bytes_written: BTreeMap::new(),
+            written_rows: BTreeMap::new(),
+            failures: Vec::new(),
+            mismatches: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn resumes_exports_with_another_end_block() {
+        let checkpoint = checkpoint(params(0, 100), 50);
+
+        assert!(checkpoint.resumes(&params(0, 100)));
+        assert!(checkpoint.resumes(&params(0, 1000)));
+        assert!(checkpoint.resumes(&params(0, 49)));
+        assert!(!checkpoint.resumes(&params(0, 48)));
+        assert!(!checkpoint.resumes(&params(1, 100)));
+        assert!(!checkpoint.resumes(&ExportParams {
+            net_transfers: true,
+            ..params(0, 100)
+        }));
+    }
+}
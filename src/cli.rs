@@ -0,0 +1,387 @@
+use crate::cache::CacheKind;
+
+use clap::{Args, Parser, Subcommand};
+use indoc::indoc;
+use serde::{Deserialize, Deserializer};
+use std::path::PathBuf;
+
+#[derive(Parser)]
+#[command(
+    name = "pooltogether-koinly-accounting",
+    version,
+    about = "Export PoolTogether yield liquidations to the Koinly universal CSV format",
+    after_help = indoc! {"
+        Every option can be given as a flag, an environment variable (a `.env` file in the
+        current directory is loaded first) or a key of the TOML file passed with `--config`.
+        Flags take precedence over environment variables, which take precedence over the
+        config file.
+
+        Rows are written with the Koinly universal CSV headers: Date, Sent Amount,
+        Sent Currency, Received Amount, Received Currency, Fee Amount, Fee Currency, Label,
+        TxHash, followed by Net Worth Amount, Net Worth Currency and Description when
+        `PRICE_SOURCES` is set, and by Wallet when several senders are combined in a single file.
+        Each liquidation is recorded as a trade of the prize token for the token out of its
+        pair (e.g. the vault shares rather than their underlying asset), with the transaction
+        fee (including the L1 data fee on OP stack chains) paid in ETH.
+        Transactions of the senders to the liquidation router that reverted are recorded with
+        their fee as the sent amount and the \"cost\" label when `RECORD_REVERTED` is true.
+        Prize claiming fees and draw rewards earned by the senders are recorded as received
+        prize tokens with the \"reward\" label, one row per transaction, if a prize pool, draw
+        manager or RNG relay auction is configured.
+        With `EXPORT_MODE` set to \"depositor\", prizes won by the senders are recorded instead
+        as received prize tokens with the \"income\" label, vault deposits as trades of the
+        underlying asset for vault shares and withdrawals as trades of vault shares back.
+        With `NET_TRANSFERS` set to true, each liquidation is instead recorded as the net ERC-20
+        transfers of its transaction for the sender: trades of a single token sent for each
+        token received, otherwise rows with the \"income\" label for the tokens received and
+        the \"cost\" label for the tokens sent.
+        The Net Worth columns hold the value of each row and the Description column the source
+        of its price, and the Wallet column holds the label or address of the sender.
+    "}
+)]
+pub struct Cli {
+    #[command(flatten)]
+    pub args: ConfigArgs,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Export liquidations of the sender to a Koinly CSV file.
+    /// An interrupted export with the same parameters is resumed from its checkpoint.
+    Export {
+        /// Ignore the checkpoint of an interrupted export and start over
+        #[arg(long)]
+        restart: bool,
+        /// Append the rows of the blocks after the last row of the existing CSV file
+        /// instead of overwriting it, skipping the rows already written
+        #[arg(long)]
+        append: bool,
+    },
+    /// Print the block range covering the configured timestamps
+    ResolveBlocks,
+    /// List the liquidation pairs known for the configured chain
+    ListPairs {
+        /// Discover liquidation pairs from the `PairCreated` events of the liquidation pair factory
+        #[arg(long)]
+        discover: bool,
+        /// Block from which to scan the liquidation pair factory, even if it was already scanned.
+        /// Defaults to the block after the last one scanned for this factory, or to its
+        /// deployment block
+        #[arg(long)]
+        from_block: Option<u64>,
+    },
+    /// Print the totals per currency of an exported Koinly CSV file
+    Summary {
+        /// Path of the CSV file to summarize, defaults to the configured output file
+        file: Option<PathBuf>,
+    },
+    /// Compare the balance changes of the senders over the configured period, read on-chain,
+    /// with the net amounts per currency of the exported Koinly CSV files
+    Reconcile,
+    /// Inspect, prune or invalidate the cache of the configured chain
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommand,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum CacheCommand {
+    /// Print the number of entries, size and block range of each kind of cached data
+    Inspect,
+    /// Remove the cached data of the blocks before a given block, pairs and tokens are kept
+    Prune {
+        /// First block whose data is kept
+        #[arg(long)]
+        before_block: u64,
+        /// Kind of data to remove, defaults to all of them
+        #[arg(long, value_enum)]
+        kind: Option<CacheKind>,
+    },
+    /// Remove the cached data of the blocks at or after a given block, e.g. after a reorg,
+    /// along with the pairs and tokens
+    Invalidate {
+        /// First block whose data is removed, defaults to the whole cache
+        #[arg(long, default_value_t = 0)]
+        from_block: u64,
+        /// Kind of data to remove, defaults to all of them
+        #[arg(long, value_enum)]
+        kind: Option<CacheKind>,
+    },
+}
+
+/// Options shared by every subcommand.
+/// Values are kept as strings so that they are parsed and validated in a single place by `Config`.
+#[derive(Args, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigArgs {
+    /// Path of a TOML config file using the option names in snake_case as keys
+    #[arg(long, env = "CONFIG_FILE", global = true)]
+    #[serde(skip)]
+    pub config: Option<PathBuf>,
+
+    /// RPC endpoint of the network to query, should be an archive node
+    #[arg(long, env = "HTTP_RPC", global = true)]
+    #[serde(deserialize_with = "deserialize_to_string")]
+    pub http_rpc: Option<String>,
+
+    /// Maximum number of attempts per RPC request before giving up, including the first one
+    #[arg(long, env = "RPC_MAX_ATTEMPTS", global = true)]
+    #[serde(deserialize_with = "deserialize_to_string")]
+    pub rpc_max_attempts: Option<String>,
+
+    /// Backoff in milliseconds before retrying a failed RPC request, doubled after each attempt
+    /// and randomized to avoid retrying in lockstep
+    #[arg(long, env = "RPC_INITIAL_BACKOFF_MS", global = true)]
+    #[serde(deserialize_with = "deserialize_to_string")]
+    pub rpc_initial_backoff_ms: Option<String>,
+
+    /// Maximum backoff in milliseconds between two attempts of an RPC request
+    #[arg(long, env = "RPC_MAX_BACKOFF_MS", global = true)]
+    #[serde(deserialize_with = "deserialize_to_string")]
+    pub rpc_max_backoff_ms: Option<String>,
+
+    /// Maximum number of RPC requests sent per second, unlimited if not set
+    #[arg(long, env = "RPC_REQUESTS_PER_SECOND", global = true)]
+    #[serde(deserialize_with = "deserialize_to_string")]
+    pub rpc_requests_per_second: Option<String>,
+
+    /// Minimum number of blocks queried at once for logs, the range shrinks down to it on busy
+    /// ranges or when the provider rejects a request
+    #[arg(long, env = "MIN_BLOCK_RANGE", global = true)]
+    #[serde(deserialize_with = "deserialize_to_string")]
+    pub min_block_range: Option<String>,
+
+    /// Maximum number of blocks queried at once for logs, the range grows up to it on quiet ranges
+    #[arg(long, env = "MAX_BLOCK_RANGE", global = true)]
+    #[serde(deserialize_with = "deserialize_to_string")]
+    pub max_block_range: Option<String>,
+
+    /// Maximum number of logs processed concurrently, each fetching its block and receipt
+    #[arg(long, env = "CONCURRENCY", global = true)]
+    #[serde(deserialize_with = "deserialize_to_string")]
+    pub concurrency: Option<String>,
+
+    /// Chain ID of the network to query, in decimal or hexadecimal format (e.g. 10 or 0x0A)
+    #[arg(long, env = "CHAIN_ID", global = true)]
+    #[serde(deserialize_with = "deserialize_to_string")]
+    pub chain_id: Option<String>,
+
+    /// Address of the sender for which to record liquidation transactions, or a comma-separated
+    /// list of senders optionally labeled with `label=address` (e.g. bot-1=0x...,bot-2=0x...)
+    #[arg(long, env = "SENDER_ADDRESS", global = true)]
+    #[serde(deserialize_with = "deserialize_to_string")]
+    pub sender: Option<String>,
+
+    /// Address of each liquidation matched against the senders: its "sender", its "receiver",
+    /// or "either" of them, e.g. to record the liquidations received by a cold wallet
+    #[arg(long, env = "MATCH_ON", global = true)]
+    #[serde(deserialize_with = "deserialize_to_string")]
+    pub match_on: Option<String>,
+
+    /// How the rows of several senders are written: "combined" in a single CSV file with a
+    /// Wallet column, or "split" in one CSV file per sender named after its label or address
+    #[arg(long, env = "WALLET_OUTPUT", global = true)]
+    #[serde(deserialize_with = "deserialize_to_string")]
+    pub wallet_output: Option<String>,
+
+    /// Activity of the senders that is exported: "bot" for liquidations and bot rewards, or
+    /// "depositor" for the prizes won and the vault deposits and withdrawals of depositors
+    #[arg(long, env = "EXPORT_MODE", global = true)]
+    #[serde(deserialize_with = "deserialize_to_string")]
+    pub export_mode: Option<String>,
+
+    /// Comma-separated list of vault addresses whose deposits and withdrawals are exported in
+    /// depositor mode, defaults to the source vaults of the registry and discovered liquidation pairs
+    #[arg(long, env = "VAULTS", global = true)]
+    #[serde(deserialize_with = "deserialize_to_string")]
+    pub vaults: Option<String>,
+
+    /// Timestamp in seconds of the first block to query (inclusive)
+    #[arg(long, env = "START_TIMESTAMP", global = true)]
+    #[serde(deserialize_with = "deserialize_to_string")]
+    pub start_timestamp: Option<String>,
+
+    /// Timestamp in seconds of the last block to query (exclusive)
+    #[arg(long, env = "END_TIMESTAMP", global = true)]
+    #[serde(deserialize_with = "deserialize_to_string")]
+    pub end_timestamp: Option<String>,
+
+    /// Calendar month to query in UTC (e.g. 2024-08), replaces the start and end timestamps
+    #[arg(long, env = "MONTH", global = true)]
+    #[serde(deserialize_with = "deserialize_to_string")]
+    pub month: Option<String>,
+
+    /// Path of a TOML registry of liquidation routers, pairs and tokens, replaces the default one
+    #[arg(long, env = "REGISTRY", global = true)]
+    #[serde(deserialize_with = "deserialize_to_string")]
+    pub registry: Option<String>,
+
+    /// Liquidation router address, overrides the one in the registry for the chain
+    #[arg(long, env = "LIQUIDATION_ROUTER", global = true)]
+    #[serde(deserialize_with = "deserialize_to_string")]
+    pub liquidation_router: Option<String>,
+
+    /// Liquidation pair factory address, overrides the one in the registry for the chain
+    #[arg(long, env = "LIQUIDATION_PAIR_FACTORY", global = true)]
+    #[serde(deserialize_with = "deserialize_to_string")]
+    pub liquidation_pair_factory: Option<String>,
+
+    /// Prize token address sent to liquidate yield, overrides the one in the registry for the chain
+    #[arg(long, env = "PRIZE_TOKEN", global = true)]
+    #[serde(deserialize_with = "deserialize_to_string")]
+    pub prize_token: Option<String>,
+
+    /// Record the fees of the transactions sent by the senders to the liquidation router that
+    /// reverted as cost rows, listing the transactions with Etherscan ("true" or "false")
+    #[arg(long, env = "RECORD_REVERTED", global = true)]
+    #[serde(deserialize_with = "deserialize_to_string")]
+    pub record_reverted: Option<String>,
+
+    /// Record each liquidation as the net ERC-20 flows of its transaction for the sender, e.g.
+    /// when the liquidated asset is swapped back to the prize token in the same transaction
+    /// ("true" or "false")
+    #[arg(long, env = "NET_TRANSFERS", global = true)]
+    #[serde(deserialize_with = "deserialize_to_string")]
+    pub net_transfers: Option<String>,
+
+    /// Prize pool address, overrides the one in the registry for the chain. The prize claiming
+    /// fees paid by its `ClaimedPrize` events to the senders are recorded as rewards.
+    #[arg(long, env = "PRIZE_POOL", global = true)]
+    #[serde(deserialize_with = "deserialize_to_string")]
+    pub prize_pool: Option<String>,
+
+    /// Draw manager address, overrides the one in the registry for the chain. The rewards paid
+    /// by its `DrawStarted` and `DrawFinished` events to the senders are recorded.
+    #[arg(long, env = "DRAW_MANAGER", global = true)]
+    #[serde(deserialize_with = "deserialize_to_string")]
+    pub draw_manager: Option<String>,
+
+    /// RNG relay auction address, overrides the one in the registry for the chain. The rewards
+    /// paid by its `AuctionRewardAllocated` events to the senders are recorded.
+    #[arg(long, env = "RNG_RELAY_AUCTION", global = true)]
+    #[serde(deserialize_with = "deserialize_to_string")]
+    pub rng_relay_auction: Option<String>,
+
+    /// Comma-separated sources of the prices used to fill the Net Worth columns, in order of
+    /// precedence: "csv", "chainlink" or "twap". Rows are not valued if not set.
+    #[arg(long, env = "PRICE_SOURCES", global = true)]
+    #[serde(deserialize_with = "deserialize_to_string")]
+    pub price_sources: Option<String>,
+
+    /// Path of a CSV file of daily prices with Date (YYYY-MM-DD), Currency and Price columns,
+    /// required by the "csv" price source
+    #[arg(long, env = "PRICES_FILE", global = true)]
+    #[serde(deserialize_with = "deserialize_to_string")]
+    pub prices_file: Option<String>,
+
+    /// Currency in which the prices are given and the rows are valued, defaults to USD
+    #[arg(long, env = "NET_WORTH_CURRENCY", global = true)]
+    #[serde(deserialize_with = "deserialize_to_string")]
+    pub net_worth_currency: Option<String>,
+
+    /// Period in seconds over which the prices of the "twap" source are averaged, defaults to 1800
+    #[arg(long, env = "TWAP_SECONDS", global = true)]
+    #[serde(deserialize_with = "deserialize_to_string")]
+    pub twap_seconds: Option<String>,
+
+    /// Age in seconds above which the answer of a Chainlink feed is considered stale and the next
+    /// price source is tried, defaults to 86400
+    #[arg(long, env = "CHAINLINK_HEARTBEAT", global = true)]
+    #[serde(deserialize_with = "deserialize_to_string")]
+    pub chainlink_heartbeat: Option<String>,
+
+    /// Etherscan API key, used as a fast path to resolve blocks from timestamps
+    /// and to list the transactions of the senders
+    #[arg(long, env = "ETHERSCAN_API_KEY", global = true)]
+    #[serde(deserialize_with = "deserialize_to_string")]
+    pub etherscan_api_key: Option<String>,
+
+    /// Directory in which data resolved on-chain is cached between runs
+    #[arg(long, env = "CACHE_DIR", global = true)]
+    #[serde(deserialize_with = "deserialize_to_string")]
+    pub cache_dir: Option<String>,
+
+    /// What to do when a log fails to be processed: "abort" the export, or "continue" and
+    /// write the failures to a report next to the Koinly CSV file
+    #[arg(long, env = "ON_ERROR", global = true)]
+    #[serde(deserialize_with = "deserialize_to_string")]
+    pub on_error: Option<String>,
+
+    /// Path of the Koinly CSV file, formatted with the start date (e.g. ./results/%Y-%m.csv)
+    #[arg(long, env = "OUTPUT", global = true)]
+    #[serde(deserialize_with = "deserialize_to_string")]
+    pub output: Option<String>,
+}
+
+impl ConfigArgs {
+    /// Fill the options that are not set with the ones from `other`
+    pub fn or(self, other: ConfigArgs) -> ConfigArgs {
+        ConfigArgs {
+            config: self.config.or(other.config),
+            http_rpc: self.http_rpc.or(other.http_rpc),
+            rpc_max_attempts: self.rpc_max_attempts.or(other.rpc_max_attempts),
+            rpc_initial_backoff_ms: self.rpc_initial_backoff_ms.or(other.rpc_initial_backoff_ms),
+            rpc_max_backoff_ms: self.rpc_max_backoff_ms.or(other.rpc_max_backoff_ms),
+            rpc_requests_per_second: self
+                .rpc_requests_per_second
+                .or(other.rpc_requests_per_second),
+            min_block_range: self.min_block_range.or(other.min_block_range),
+            max_block_range: self.max_block_range.or(other.max_block_range),
+            concurrency: self.concurrency.or(other.concurrency),
+            chain_id: self.chain_id.or(other.chain_id),
+            sender: self.sender.or(other.sender),
+            match_on: self.match_on.or(other.match_on),
+            export_mode: self.export_mode.or(other.export_mode),
+            vaults: self.vaults.or(other.vaults),
+            wallet_output: self.wallet_output.or(other.wallet_output),
+            start_timestamp: self.start_timestamp.or(other.start_timestamp),
+            end_timestamp: self.end_timestamp.or(other.end_timestamp),
+            month: self.month.or(other.month),
+            registry: self.registry.or(other.registry),
+            liquidation_router: self.liquidation_router.or(other.liquidation_router),
+            liquidation_pair_factory: self
+                .liquidation_pair_factory
+                .or(other.liquidation_pair_factory),
+            prize_token: self.prize_token.or(other.prize_token),
+            record_reverted: self.record_reverted.or(other.record_reverted),
+            net_transfers: self.net_transfers.or(other.net_transfers),
+            prize_pool: self.prize_pool.or(other.prize_pool),
+            draw_manager: self.draw_manager.or(other.draw_manager),
+            rng_relay_auction: self.rng_relay_auction.or(other.rng_relay_auction),
+            price_sources: self.price_sources.or(other.price_sources),
+            prices_file: self.prices_file.or(other.prices_file),
+            net_worth_currency: self.net_worth_currency.or(other.net_worth_currency),
+            twap_seconds: self.twap_seconds.or(other.twap_seconds),
+            chainlink_heartbeat: self.chainlink_heartbeat.or(other.chainlink_heartbeat),
+            etherscan_api_key: self.etherscan_api_key.or(other.etherscan_api_key),
+            cache_dir: self.cache_dir.or(other.cache_dir),
+            on_error: self.on_error.or(other.on_error),
+            output: self.output.or(other.output),
+        }
+    }
+}
+
+/// Accept both strings and numbers in the config file, e.g. `chain_id = 10` or `chain_id = "0x0A"`.
+/// Arrays are joined with commas, e.g. `sender = ["bot-1=0x...", "bot-2=0x..."]`.
+fn deserialize_to_string<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    fn to_string(value: toml::Value) -> String {
+        match value {
+            toml::Value::String(value) => value,
+            toml::Value::Array(values) => values
+                .into_iter()
+                .map(to_string)
+                .collect::<Vec<_>>()
+                .join(","),
+            value => value.to_string(),
+        }
+    }
+
+    Ok(Some(to_string(toml::Value::deserialize(deserializer)?)))
+}
@@ -0,0 +1,1241 @@
+use crate::{
+    append::{ExistingRows, RowKey, WrittenRows},
+    blocks::BlockResolver,
+    bot::{
+        koinly_headers, AuctionRewardAllocated, Bot, ClaimedPrize, Deposit, DrawFinished,
+        DrawStarted, ExportItem, LiquidationPair, Withdraw,
+    },
+    cache::{Cache, CacheKind},
+    checkpoint::{Checkpoint, ExportParams},
+    cli::{CacheCommand, ConfigArgs},
+    config::Config,
+    discovery::PairResolver,
+    errors::{ErrorPolicy, FailureReport, LogFailure},
+    helpers::push_unique,
+    init::print_banner,
+    log_info_cyan,
+    reconcile::BalanceReader,
+    retry::{RetryConfig, RetryLayer, RetryService},
+    scanner::LogScanner,
+    summary::{format_amount, Summary},
+    tokens::TokenResolver,
+    transactions::get_failed_transactions,
+    wallets::ExportMode,
+};
+
+use alloy::{
+    network::AnyNetwork,
+    primitives::{Address, U64},
+    providers::{Provider, ProviderBuilder},
+    rpc::{client::ClientBuilder, types::Filter},
+    sol_types::SolEvent,
+    transports::{
+        http::{Client as HttpClient, Http},
+        Transport,
+    },
+};
+use alloy_chains::Chain;
+use colored::Colorize;
+use eyre::{eyre, Result};
+use foundry_block_explorers::Client;
+use futures_util::{stream, StreamExt};
+use indicatif::{MultiProgress, ProgressBar};
+use reqwest::Url;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Transport of the provider, retrying failed requests and rate limiting them
+type RetryTransport = RetryService<Http<HttpClient>>;
+
+/// Build the provider used to query the network
+fn build_provider(
+    http_rpc: Url,
+    retry: RetryConfig,
+) -> Arc<impl Provider<RetryTransport, AnyNetwork> + Clone> {
+    let client = ClientBuilder::default()
+        .layer(RetryLayer::new(retry))
+        .http(http_rpc);
+
+    Arc::new(
+        ProviderBuilder::new()
+            .with_recommended_fillers()
+            .network::<AnyNetwork>()
+            .on_client(client),
+    )
+}
+
+/// Build the Etherscan client if an API key is configured
+fn build_etherscan(chain_id: U64, etherscan_api_key: Option<String>) -> Result<Option<Client>> {
+    match etherscan_api_key {
+        Some(api_key) => Ok(Some(Client::new(
+            Chain::from_id(chain_id.try_into()?),
+            api_key,
+        )?)),
+        None => Ok(None),
+    }
+}
+
+/// Filters of the logs exported for the wallets in the configured mode
+fn export_filters(
+    config: &Config,
+    wallets: &[Address],
+    pairs: &[Address],
+    vaults: &[Address],
+) -> Vec<Filter> {
+    let wallet_topics: Vec<_> = wallets.iter().map(|wallet| wallet.into_word()).collect();
+
+    match config.export_mode {
+        ExportMode::Bot => {
+            // Liquidations of the wallets, only returning the logs whose sender and/or receiver
+            // topic is one of the wallets.
+            let mut filters = config.match_on.filters(
+                Filter::new().address(config.liquidation_router).event(
+                    "SwappedExactAmountOut(address,address,address,uint256,uint256,uint256,uint256)",
+                ),
+                wallets,
+                2,
+            );
+
+            // Liquidations swapping a pair directly, which are not seen by the router. Only the
+            // events of the known pairs are fetched, since any contract can emit them.
+            if !pairs.is_empty() {
+                filters.extend(
+                    config.match_on.filters(
+                        Filter::new().address(pairs.to_vec()).event_signature(
+                            LiquidationPair::SwappedExactAmountOut::SIGNATURE_HASH,
+                        ),
+                        wallets,
+                        1,
+                    ),
+                );
+            }
+
+            // The fee recipient of ClaimedPrize events is not indexed, so all the claims of the
+            // prize pool are fetched and those paying the wallets are kept by the bot.
+            if let Some(prize_pool) = config.prize_pool {
+                filters.push(
+                    Filter::new()
+                        .address(prize_pool)
+                        .event_signature(ClaimedPrize::SIGNATURE_HASH),
+                );
+            }
+
+            // The recipient of draw rewards is indexed, so only the rewards of the wallets are
+            // fetched.
+            if let Some(draw_manager) = config.draw_manager {
+                filters.push(
+                    Filter::new()
+                        .address(draw_manager)
+                        .event_signature(vec![
+                            DrawStarted::SIGNATURE_HASH,
+                            DrawFinished::SIGNATURE_HASH,
+                        ])
+                        .topic2(wallet_topics.clone()),
+                );
+            }
+
+            if let Some(rng_relay_auction) = config.rng_relay_auction {
+                filters.push(
+                    Filter::new()
+                        .address(rng_relay_auction)
+                        .event_signature(AuctionRewardAllocated::SIGNATURE_HASH)
+                        .topic2(wallet_topics),
+                );
+            }
+
+            filters
+        }
+        ExportMode::Depositor => {
+            // Deposits of the shares owned by the wallets and withdrawals of the shares they owned
+            // or of the assets they received.
+            let vault_filter = Filter::new().address(vaults.to_vec());
+
+            let mut filters = vec![
+                vault_filter
+                    .clone()
+                    .event_signature(Deposit::SIGNATURE_HASH)
+                    .topic2(wallet_topics.clone()),
+                vault_filter
+                    .clone()
+                    .event_signature(Withdraw::SIGNATURE_HASH)
+                    .topic2(wallet_topics.clone()),
+                vault_filter
+                    .event_signature(Withdraw::SIGNATURE_HASH)
+                    .topic3(wallet_topics.clone()),
+            ];
+
+            // Prizes won by the wallets, the winner being indexed.
+            if let Some(prize_pool) = config.prize_pool {
+                filters.push(
+                    Filter::new()
+                        .address(prize_pool)
+                        .event_signature(ClaimedPrize::SIGNATURE_HASH)
+                        .topic2(wallet_topics),
+                );
+            }
+
+            filters
+        }
+    }
+}
+
+/// Build the block resolver, using Etherscan as a fast path if an API key is configured
+fn build_block_resolver<T, P>(
+    provider: Arc<P>,
+    chain_id: U64,
+    etherscan_api_key: Option<String>,
+) -> Result<BlockResolver<T, P>>
+where
+    T: Transport + Clone,
+    P: Provider<T, AnyNetwork>,
+{
+    Ok(BlockResolver::new(
+        provider,
+        build_etherscan(chain_id, etherscan_api_key)?,
+    ))
+}
+
+/// Export liquidations of the senders to Koinly CSV files,
+/// resuming an interrupted export from its checkpoint unless `restart` is set.
+/// With `append`, only the blocks after the last row of the existing files are exported.
+pub async fn export(
+    args: ConfigArgs,
+    restart: bool,
+    append: bool,
+    multi_progress: MultiProgress,
+) -> Result<()> {
+    let config = Config::from_args(args)?;
+
+    print_banner();
+
+    let provider = build_provider(config.http_rpc.clone(), config.retry.clone());
+
+    let block_resolver = build_block_resolver(
+        provider.clone(),
+        config.chain_id,
+        config.etherscan_api_key.clone(),
+    )?;
+
+    // Only data of finalized blocks is cached, since more recent blocks could still be reorged.
+    let cache = Arc::new(
+        Cache::new(&config.cache_dir, config.registry.chain_id)
+            .with_safe_block(block_resolver.get_finalized_block().await?),
+    );
+
+    let bot = Bot::new(provider.clone(), config.clone(), cache.clone())?;
+
+    let (from_block, to_block) = block_resolver
+        .get_block_range(config.start_timestamp, config.end_timestamp)
+        .await?;
+
+    log_info_cyan!("Querying blocks {} to {}", from_block, to_block);
+
+    let files = config.export_files()?;
+    let checkpoint_path = Checkpoint::path(&config.output_path()?);
+
+    let mut params = ExportParams {
+        chain_id: config.registry.chain_id,
+        senders: config.senders.clone(),
+        wallet_output: config.wallet_output,
+        match_on: config.match_on,
+        liquidation_router: config.liquidation_router,
+        prize_token: config.prize_token,
+        record_reverted: config.record_reverted,
+        net_transfers: config.net_transfers,
+        prize_pool: config.prize_pool,
+        draw_manager: config.draw_manager,
+        rng_relay_auction: config.rng_relay_auction,
+        export_mode: config.export_mode,
+        vaults: config.vaults.clone(),
+        prices: config.prices.clone(),
+        from_block,
+        to_block,
+    };
+
+    let checkpoint = if restart {
+        None
+    } else {
+        Checkpoint::load(&checkpoint_path)?
+    };
+
+    // File index and Wallet column value of the rows of each wallet.
+    let routes: HashMap<Address, (usize, Option<String>)> = files
+        .iter()
+        .enumerate()
+        .flat_map(|(index, file)| {
+            file.wallets.iter().map(move |wallet| {
+                (
+                    wallet.address,
+                    (index, file.wallet_column.then(|| wallet.name())),
+                )
+            })
+        })
+        .collect();
+
+    let mut existing_rows: Vec<ExistingRows> =
+        files.iter().map(|_| ExistingRows::default()).collect();
+
+    let (outputs, next_block, mut failure_report, mut written_rows) = match checkpoint {
+        Some(checkpoint) if checkpoint.resumes(&params) => {
+            log_info_cyan!(
+                "Resuming export into {} CSV files from block {}",
+                files.len(),
+                checkpoint.next_block
+            );
+
+            (
+                files
+                    .iter()
+                    .map(|file| checkpoint.truncate_csv(&file.path))
+                    .collect::<Result<Vec<_>>>()?,
+                checkpoint.next_block,
+                FailureReport {
+                    failures: checkpoint.failures,
+                    mismatches: checkpoint.mismatches,
+                },
+                files
+                    .iter()
+                    .map(|file| {
+                        checkpoint
+                            .written_rows
+                            .get(&file.path)
+                            .cloned()
+                            .unwrap_or_default()
+                    })
+                    .collect::<Vec<_>>(),
+            )
+        }
+        checkpoint if append => {
+            // Rows written by an interrupted incremental export after its checkpoint may be
+            // incomplete, they are dropped and produced again.
+            let (outputs, failures, mismatches, mut checkpoint_rows) = match checkpoint {
+                Some(checkpoint) if checkpoint.params.same_wallets(&params) => (
+                    files
+                        .iter()
+                        .map(|file| checkpoint.truncate_csv(&file.path))
+                        .collect::<Result<Vec<_>>>()?,
+                    checkpoint.failures,
+                    checkpoint.mismatches,
+                    Some(checkpoint.written_rows),
+                ),
+                _ => (
+                    files
+                        .iter()
+                        .map(|file| {
+                            std::fs::OpenOptions::new()
+                                .write(true)
+                                .create(true)
+                                .truncate(false)
+                                .open(&file.path)
+                        })
+                        .collect::<std::io::Result<Vec<_>>>()?,
+                    Vec::new(),
+                    Vec::new(),
+                    None,
+                ),
+            };
+
+            // The blocks of the last row of each file are scanned again, their rows already
+            // written are skipped. Files without rows are exported from the first block.
+            let mut next_block = to_block + 1;
+
+            for (file, rows) in files.iter().zip(existing_rows.iter_mut()) {
+                *rows = ExistingRows::from_koinly_csv(
+                    &file.path,
+                    checkpoint_rows
+                        .as_mut()
+                        .map(|written_rows| written_rows.remove(&file.path).unwrap_or_default()),
+                )?;
+
+                if !rows.headers.is_empty()
+                    && rows.headers
+                        != koinly_headers(config.prices.is_enabled(), file.wallet_column)
+                {
+                    return Err(eyre!(
+                        "CSV file {} was written with different columns, export it again without --append",
+                        file.path
+                    ));
+                }
+
+                let file_next_block = match rows.last_timestamp {
+                    Some(timestamp) => std::cmp::max(
+                        from_block,
+                        block_resolver.first_block_at_or_after(timestamp).await?,
+                    ),
+                    None => from_block,
+                };
+
+                next_block = next_block.min(file_next_block);
+
+                log_info_cyan!(
+                    "Appending to CSV file {} with {} rows",
+                    file.path,
+                    rows.len()
+                );
+            }
+
+            log_info_cyan!("Appending rows from block {}", next_block);
+
+            params.from_block = next_block;
+
+            (
+                outputs,
+                next_block,
+                FailureReport {
+                    failures,
+                    mismatches,
+                },
+                existing_rows
+                    .iter()
+                    .map(|rows| rows.written.clone())
+                    .collect(),
+            )
+        }
+        checkpoint => {
+            if checkpoint.is_some() {
+                log::warn!(
+                    "Ignoring checkpoint {} of an export with different parameters",
+                    checkpoint_path.display()
+                );
+            }
+
+            let outputs = files
+                .iter()
+                .map(|file| {
+                    log_info_cyan!("Creating or overwriting CSV file: {}", file.path);
+
+                    // Opens the file in write mode and creates it if it doesn't exist.
+                    // If the file already exists, it will be overwritten.
+                    std::fs::OpenOptions::new()
+                        .write(true)
+                        .create(true)
+                        .truncate(true)
+                        .open(&file.path)
+                })
+                .collect::<std::io::Result<Vec<_>>>()?;
+
+            (
+                outputs,
+                from_block,
+                FailureReport::default(),
+                vec![WrittenRows::default(); files.len()],
+            )
+        }
+    };
+
+    let progress_bar = multi_progress.add(ProgressBar::new(to_block - from_block + 1));
+    progress_bar.inc(next_block.saturating_sub(from_block));
+
+    // Headers were already written if the file is not empty.
+    let mut writers = outputs
+        .into_iter()
+        .map(|mut file| {
+            let has_headers = file.seek(SeekFrom::End(0))? == 0;
+
+            Ok(csv::WriterBuilder::new()
+                .has_headers(has_headers)
+                .from_writer(file))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    // Depositors are exported from the prize vaults liquidated by the known pairs, rather than
+    // from every ERC-4626 vault the wallets ever used.
+    let vaults = if config.export_mode == ExportMode::Depositor && config.vaults.is_empty() {
+        let vaults = bot.prize_vaults().await;
+
+        if vaults.is_empty() {
+            return Err(eyre!(
+                "No prize vault is known, set \"VAULTS\" or discover the liquidation pairs with list-pairs --discover"
+            ));
+        }
+
+        log_info_cyan!(
+            "Exporting the deposits and withdrawals of {} prize vaults",
+            vaults.len()
+        );
+
+        vaults
+    } else {
+        config.vaults.clone()
+    };
+
+    let pairs = bot.known_pairs();
+
+    if config.export_mode == ExportMode::Bot && pairs.is_empty() {
+        log::warn!(
+            "No liquidation pair is known, liquidations swapping a pair directly are not exported. Discover them with list-pairs --discover"
+        );
+    }
+
+    let filters = export_filters(
+        &config,
+        &routes.keys().copied().collect::<Vec<_>>(),
+        &pairs,
+        &vaults,
+    );
+
+    // Reverted transactions emit no logs, so they are listed with Etherscan and merged with the
+    // logs of each chunk.
+    let mut failed_transactions = if config.record_reverted {
+        let etherscan = build_etherscan(config.chain_id, config.etherscan_api_key.clone())?
+            .ok_or_else(|| eyre!("\"RECORD_REVERTED\" requires \"ETHERSCAN_API_KEY\""))?;
+
+        let transactions = get_failed_transactions(
+            &etherscan,
+            &config
+                .senders
+                .iter()
+                .map(|wallet| wallet.address)
+                .collect::<Vec<_>>(),
+            config.liquidation_router,
+            next_block,
+            to_block,
+        )
+        .await?;
+
+        log_info_cyan!(
+            "Found {} failed transactions sent to the liquidation router",
+            transactions.len()
+        );
+
+        transactions
+    } else {
+        Vec::new()
+    }
+    .into_iter()
+    .peekable();
+
+    let mut scanner = LogScanner::new(
+        provider.clone(),
+        filters,
+        cache,
+        next_block,
+        to_block,
+        config.chunks,
+    );
+
+    // Loop through the blocks in the range by chunks whose size adapts to the number of logs.
+    while let Some(chunk) = scanner.next_chunk().await? {
+        let mut items = ExportItem::from_logs(chunk.logs);
+
+        while let Some(transaction) =
+            failed_transactions.next_if(|transaction| transaction.block_number <= chunk.to_block)
+        {
+            items.push(ExportItem::FailedTransaction(transaction));
+        }
+
+        items.sort_by_key(ExportItem::position);
+
+        // Items are decoded concurrently, `buffered` yields the results in the order of the items
+        // so that rows are written in (block, transaction index, log index) order.
+        let mut results = stream::iter(items)
+            .map(|item| {
+                let bot = &bot;
+
+                async move {
+                    let (block_number, _, log_index) = item.position();
+                    let tx_hash = item.tx_hash();
+
+                    (block_number, log_index, tx_hash, bot.decode(item).await)
+                }
+            })
+            .buffered(config.concurrency);
+
+        while let Some((block_number, log_index, tx_hash, result)) = results.next().await {
+            match result {
+                Ok(rows) => {
+                    // Position of the next row of the item in each file.
+                    let mut positions = vec![0; files.len()];
+
+                    for koinly_data in rows {
+                        let (index, wallet) = &routes[&koinly_data.wallet];
+
+                        let key = tx_hash.map(|tx_hash| RowKey {
+                            tx_hash,
+                            log_index,
+                            row: positions[*index],
+                        });
+                        positions[*index] += 1;
+
+                        if let Some(key) = key {
+                            let already_written = existing_rows[*index].contains(&key);
+
+                            written_rows[*index].push(koinly_data.date(), key);
+
+                            if already_written {
+                                continue;
+                            }
+                        }
+
+                        bot.write_to_koinly_csv(
+                            &mut writers[*index],
+                            koinly_data,
+                            wallet.as_deref(),
+                        )
+                        .await?;
+                    }
+                }
+                Err(error) if config.on_error == ErrorPolicy::Continue => {
+                    log::warn!(
+                        "Failed to process log {:?} of block {:?}: {}",
+                        log_index,
+                        block_number,
+                        error
+                    );
+
+                    failure_report.push(LogFailure {
+                        block_number,
+                        log_index,
+                        tx_hash,
+                        error: error.to_string(),
+                    });
+                }
+                Err(error) => {
+                    return Err(eyre!(
+                        "Failed to process log {:?} of block {:?} in transaction {:?}: {}",
+                        log_index,
+                        block_number,
+                        tx_hash,
+                        error
+                    ))
+                }
+            }
+        }
+
+        // Blocks scanned again when appending may report mismatches already recorded.
+        for mismatch in bot.take_mismatches() {
+            if !failure_report.mismatches.iter().any(|recorded| {
+                (recorded.tx_hash, recorded.log_index, recorded.token)
+                    == (mismatch.tx_hash, mismatch.log_index, mismatch.token)
+            }) {
+                failure_report.mismatches.push(mismatch);
+            }
+        }
+
+        // Save the progress once all the rows of the chunk are written to the CSV files.
+        let mut bytes_written = BTreeMap::new();
+
+        for (file, wtr) in files.iter().zip(writers.iter_mut()) {
+            wtr.flush()?;
+            bytes_written.insert(file.path.clone(), wtr.get_ref().metadata()?.len());
+        }
+
+        Checkpoint {
+            params: params.clone(),
+            next_block: chunk.to_block + 1,
+            bytes_written,
+            written_rows: files
+                .iter()
+                .map(|file| file.path.clone())
+                .zip(written_rows.iter().cloned())
+                .collect(),
+            failures: failure_report.failures.clone(),
+            mismatches: failure_report.mismatches.clone(),
+        }
+        .save(&checkpoint_path)?;
+
+        // Advance the progress bar by the number of blocks of the chunk.
+        progress_bar.inc(chunk.to_block - chunk.from_block + 1);
+    }
+
+    for wtr in writers.iter_mut() {
+        wtr.flush()?;
+    }
+
+    // The rows are saved before removing the checkpoint, which holds them otherwise.
+    for (file, rows) in files.iter().zip(written_rows.iter()) {
+        rows.save(&file.path)?;
+    }
+
+    Checkpoint::remove(&checkpoint_path)?;
+
+    log_info_cyan!("Transactions processed!");
+
+    scanner.stats.report();
+
+    progress_bar.finish();
+    multi_progress.remove(&progress_bar);
+
+    let output_stem = config.output_path()?.trim_end_matches(".csv").to_string();
+    let mismatches_path = format!("{}.mismatches.csv", output_stem);
+    let failures_path = format!("{}.failures.csv", output_stem);
+
+    if failure_report.mismatches.is_empty() {
+        remove_stale_report(&mismatches_path)?;
+    } else {
+        failure_report.write_mismatches_to_csv(&mismatches_path)?;
+
+        log::warn!(
+            "{} liquidations differ from the net transfers of their wallet, see {}",
+            failure_report.mismatches.len(),
+            mismatches_path
+        );
+    }
+
+    if failure_report.is_empty() {
+        remove_stale_report(&failures_path)?;
+    } else {
+        failure_report.write_to_csv(&failures_path)?;
+
+        log::warn!(
+            "{} logs failed to be processed, see {}",
+            failure_report.failures.len(),
+            failures_path
+        );
+    }
+
+    Ok(())
+}
+
+/// Remove a report left over by a previous run, so that it isn't mistaken for one of the current run
+fn remove_stale_report(path: &str) -> Result<()> {
+    match std::fs::remove_file(path) {
+        Ok(()) => {
+            log_info_cyan!("Removed stale report {}", path);
+            Ok(())
+        }
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(error) => Err(eyre!("Failed to remove stale report {}: {}", path, error)),
+    }
+}
+
+/// Print the block range covering the configured timestamps
+pub async fn resolve_blocks(args: ConfigArgs) -> Result<()> {
+    let args = args.with_config_file()?;
+    let (start_timestamp, end_timestamp) = args.timestamps()?;
+
+    let provider = build_provider(args.http_rpc()?, args.retry()?);
+    let block_resolver =
+        build_block_resolver(provider, args.chain_id()?, args.etherscan_api_key())?;
+
+    let (from_block, to_block) = block_resolver
+        .get_block_range(start_timestamp, end_timestamp)
+        .await?;
+
+    log_info_cyan!(
+        "Timestamps {} to {} cover blocks {} to {}",
+        start_timestamp,
+        end_timestamp,
+        from_block,
+        to_block
+    );
+
+    Ok(())
+}
+
+/// List the liquidation pairs known for the configured chain,
+/// optionally discovering new ones from the liquidation pair factory
+pub async fn list_pairs(args: ConfigArgs, discover: bool, from_block: Option<u64>) -> Result<()> {
+    let args = args.with_config_file()?;
+    let chain_id = args.chain_id()?;
+
+    let registry = args.registry()?;
+    let chain = registry.chain(chain_id)?;
+
+    log_info_cyan!(
+        "Liquidation router on {}: {}",
+        chain.name,
+        args.liquidation_router(chain)?
+    );
+
+    for liquidation_pair in &chain.liquidation_pairs {
+        log_info_cyan!(
+            "Liquidation pair {} -> {} ({})",
+            liquidation_pair.address,
+            chain
+                .get_token(liquidation_pair.underlying_asset)
+                .map_or("unknown", |token| token.symbol.as_str()),
+            liquidation_pair.underlying_asset
+        );
+    }
+
+    if !discover {
+        return Ok(());
+    }
+
+    let factory = args.liquidation_pair_factory(chain)?.ok_or_else(|| {
+        eyre!(
+            "No liquidation pair factory address found for the given chain ID: {}, set \"LIQUIDATION_PAIR_FACTORY\"",
+            chain_id
+        )
+    })?;
+
+    let provider = build_provider(args.http_rpc()?, args.retry()?);
+    let pairs = PairResolver::new(provider.clone(), chain.clone(), &args.cache_dir())?;
+    let tokens = TokenResolver::new(provider.clone(), chain.clone(), &args.cache_dir())?;
+
+    pairs
+        .discover(
+            factory,
+            from_block,
+            chain
+                .liquidation_pair_factory_start_block
+                .unwrap_or_default(),
+            provider.get_block_number().await?,
+            // Scans resume from the last block scanned for the factory, so their chunks are not cached.
+            Arc::new(Cache::new(&args.cache_dir(), chain.chain_id)),
+            args.chunks()?,
+        )
+        .await?;
+
+    for liquidation_pair in pairs.discovered_pairs() {
+        log_info_cyan!(
+            "Discovered liquidation pair {} -> {} ({}, vault {})",
+            liquidation_pair.address,
+            tokens
+                .get_token(liquidation_pair.underlying_asset)
+                .await?
+                .symbol,
+            liquidation_pair.underlying_asset,
+            liquidation_pair.source
+        );
+    }
+
+    Ok(())
+}
+
+/// Print the totals per currency of an exported Koinly CSV file
+pub async fn summary(args: ConfigArgs, file: Option<PathBuf>) -> Result<()> {
+    let path = match file {
+        Some(path) => path,
+        None => PathBuf::from(args.with_config_file()?.output_path()?),
+    };
+
+    let summary = Summary::from_koinly_csv(Path::new(&path))?;
+
+    log_info_cyan!("{} rows in {}", summary.rows, path.display());
+
+    for (symbol, totals) in summary.currencies {
+        log_info_cyan!(
+            "{}: sent {}, received {}, fees {}, net {}",
+            symbol,
+            format_amount(totals.sent),
+            format_amount(totals.received),
+            format_amount(totals.fees),
+            format_amount(totals.net())
+        );
+    }
+
+    Ok(())
+}
+
+/// Compare the balance changes of the senders over the configured period, read on-chain,
+/// with the net amounts per currency of their exported Koinly CSV files
+pub async fn reconcile(args: ConfigArgs) -> Result<()> {
+    let config = Config::from_args(args)?;
+
+    let provider = build_provider(config.http_rpc.clone(), config.retry.clone());
+    let block_resolver = build_block_resolver(
+        provider.clone(),
+        config.chain_id,
+        config.etherscan_api_key.clone(),
+    )?;
+
+    let (from_block, to_block) = block_resolver
+        .get_block_range(config.start_timestamp, config.end_timestamp)
+        .await?;
+
+    // Only tokens already known are matched, since symbols can't be resolved to addresses on-chain.
+    // The tokens of the registry and the prize token are reconciled even without rows, so that
+    // transfers missing from the export are reported.
+    let tokens = TokenResolver::new(provider.clone(), config.registry.clone(), &config.cache_dir)?;
+    let mut tracked_tokens = config.registry.tokens.clone();
+    push_unique(
+        &mut tracked_tokens,
+        tokens.get_token(config.prize_token).await?,
+        |token| token.address,
+    );
+
+    let balances = BalanceReader::new(
+        provider,
+        config.registry.fee_token_symbol.clone(),
+        tokens.known_tokens(),
+        tracked_tokens,
+    );
+
+    log_info_cyan!(
+        "Reconciling balances before block {} and at block {}",
+        from_block,
+        to_block
+    );
+
+    let mut differences = 0;
+
+    for file in config.export_files()? {
+        for wallet in &file.wallets {
+            let name = wallet.name();
+            let summary = Summary::from_koinly_csv_for_wallet(
+                Path::new(&file.path),
+                file.wallet_column.then_some(name.as_str()),
+            )?;
+
+            let reconciliation = balances
+                .reconcile(wallet.address, from_block, to_block, &summary)
+                .await?;
+
+            for asset in reconciliation.assets {
+                log_info_cyan!(
+                    "{} {}: balance {} -> {}, recorded {}, unexplained {}",
+                    name,
+                    asset.symbol,
+                    format_amount(asset.start_balance),
+                    format_amount(asset.end_balance),
+                    format_amount(asset.recorded),
+                    format_amount(asset.unexplained())
+                );
+
+                if !asset.unexplained().is_zero() {
+                    differences += 1;
+                }
+            }
+
+            for symbol in reconciliation.unmatched {
+                log::warn!(
+                    "{} {}: not reconciled, no single token with this symbol in the registry or the token cache",
+                    name,
+                    symbol
+                );
+            }
+        }
+    }
+
+    if differences > 0 {
+        return Err(eyre!(
+            "{} balances differ from the exported rows, some transactions are missing from the export",
+            differences
+        ));
+    }
+
+    log_info_cyan!("All reconciled balances match the exported rows");
+
+    Ok(())
+}
+
+/// Inspect, prune or invalidate the cache of the configured chain
+pub fn cache(args: ConfigArgs, command: CacheCommand) -> Result<()> {
+    let args = args.with_config_file()?;
+    let chain_id: u64 = args.chain_id()?.try_into()?;
+    let cache = Cache::new(&args.cache_dir(), chain_id);
+
+    let (kinds, removed) = match command {
+        CacheCommand::Inspect => {
+            for kind in CacheKind::ALL {
+                let stats = cache.stats(kind)?;
+
+                if let (Some(first_block), Some(last_block)) = (stats.first_block, stats.last_block)
+                {
+                    log_info_cyan!(
+                        "{:?}: {} entries, {} bytes, blocks {} to {}",
+                        kind,
+                        stats.entries,
+                        stats.bytes,
+                        first_block,
+                        last_block
+                    );
+                } else if stats.entries > 0 {
+                    log_info_cyan!("{:?}: {} bytes", kind, stats.bytes);
+                } else {
+                    log_info_cyan!("{:?}: empty", kind);
+                }
+            }
+
+            return Ok(());
+        }
+        CacheCommand::Prune { before_block, kind } => {
+            let kinds = kind.map_or(CacheKind::ALL.to_vec(), |kind| vec![kind]);
+            let removed = kinds
+                .iter()
+                .map(|kind| cache.prune(*kind, before_block))
+                .sum::<Result<u64>>()?;
+
+            (kinds, removed)
+        }
+        CacheCommand::Invalidate { from_block, kind } => {
+            let kinds = kind.map_or(CacheKind::ALL.to_vec(), |kind| vec![kind]);
+            let removed = kinds
+                .iter()
+                .map(|kind| cache.invalidate(*kind, from_block))
+                .sum::<Result<u64>>()?;
+
+            (kinds, removed)
+        }
+    };
+
+    log_info_cyan!("Removed {} cache entries of {:?}", removed, kinds);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bot::SwappedExactAmountOut,
+        mock_rpc::{self, MockChain, MockResponse, MockRpc, PAIR, PRIZE_TOKEN, ROUTER, VAULT},
+        transfers::Transfer,
+    };
+
+    use alloy::primitives::{address, TxHash, U256};
+    use indicatif::ProgressDrawTarget;
+    use std::sync::Mutex;
+
+    const WALLET: Address = address!("00000000000000000000000000000000000000aa");
+
+    /// Chain of 60 blocks whose wallet liquidates `PAIR` through the router at the given blocks,
+    /// receiving vault shares, with the hashes of the liquidation transactions
+    fn chain(blocks: &[u64]) -> (MockChain, Vec<TxHash>) {
+        let mut chain = MockChain::new(60).with_pair();
+        let mut tx_hashes = Vec::new();
+
+        for (index, block_number) in blocks.iter().enumerate() {
+            let amount_in = U256::from(10 + index);
+            let amount_out = U256::from(1000 + index);
+
+            let tx_hash = chain.transaction(
+                *block_number,
+                WALLET,
+                vec![
+                    (
+                        PRIZE_TOKEN,
+                        Transfer {
+                            from: WALLET,
+                            to: PAIR,
+                            value: amount_in,
+                        }
+                        .encode_log_data(),
+                    ),
+                    (
+                        VAULT,
+                        Transfer {
+                            from: PAIR,
+                            to: WALLET,
+                            value: amount_out,
+                        }
+                        .encode_log_data(),
+                    ),
+                    (
+                        ROUTER,
+                        SwappedExactAmountOut {
+                            liquidationPair: PAIR,
+                            sender: WALLET,
+                            receiver: WALLET,
+                            amountOut: amount_out,
+                            amountInMax: amount_in,
+                            amountIn: amount_in,
+                            deadline: U256::ZERO,
+                        }
+                        .encode_log_data(),
+                    ),
+                ],
+            );
+
+            tx_hashes.push(tx_hash);
+        }
+
+        (chain, tx_hashes)
+    }
+
+    /// Options of an export of the wallet into `dir` by chunks of 10 blocks
+    fn args(rpc: &MockRpc, dir: &Path, end_block: u64) -> ConfigArgs {
+        ConfigArgs {
+            http_rpc: Some(rpc.url.to_string()),
+            rpc_max_attempts: Some("1".to_string()),
+            min_block_range: Some("10".to_string()),
+            max_block_range: Some("10".to_string()),
+            chain_id: Some("10".to_string()),
+            sender: Some(WALLET.to_string()),
+            start_timestamp: Some(MockChain::timestamp(0).to_string()),
+            end_timestamp: Some(MockChain::timestamp(end_block).to_string()),
+            registry: Some(mock_rpc::write_registry(dir).display().to_string()),
+            cache_dir: Some(dir.join("cache").display().to_string()),
+            output: Some(dir.join("output.csv").display().to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn hidden_progress() -> MultiProgress {
+        MultiProgress::with_draw_target(ProgressDrawTarget::hidden())
+    }
+
+    #[tokio::test]
+    async fn resumes_interrupted_exports() {
+        let blocks = [5, 12, 15, 25, 55];
+        let dir = std::env::temp_dir().join(format!("export-{}", rand::random::<u64>()));
+        let straight_dir = dir.join("straight");
+        let resumed_dir = dir.join("resumed");
+
+        let rpc = chain(&blocks).0.start().await;
+        export(
+            args(&rpc, &straight_dir, 1000),
+            false,
+            false,
+            hidden_progress(),
+        )
+        .await
+        .unwrap();
+
+        // The receipt of the liquidation of block 15 fails once the one of block 12 was written,
+        // interrupting the export in its second chunk of blocks, before the end block is reached.
+        let (interrupted_chain, tx_hashes) = chain(&blocks);
+        let interrupted_tx = serde_json::json!(tx_hashes[2]);
+
+        let rpc = MockRpc::start(move |method, params, _| {
+            if method == "eth_getTransactionReceipt" && params[0] == interrupted_tx {
+                return MockResponse::Error(-32000, "interrupted".to_string());
+            }
+
+            interrupted_chain.respond(method, params)
+        })
+        .await;
+
+        assert!(export(
+            args(&rpc, &resumed_dir, 40),
+            false,
+            false,
+            hidden_progress()
+        )
+        .await
+        .is_err());
+
+        let checkpoint = Checkpoint::load(&Checkpoint::path(
+            &resumed_dir.join("output.csv").display().to_string(),
+        ))
+        .unwrap()
+        .unwrap();
+        assert_eq!(checkpoint.next_block, 10);
+
+        // The export is resumed with a later end block, from the first block of the checkpoint.
+        // The cache is cleared so that the provider sees every block scanned again.
+        std::fs::remove_dir_all(resumed_dir.join("cache")).unwrap();
+
+        let scanned_from = Arc::new(Mutex::new(Vec::new()));
+        let scanned = scanned_from.clone();
+        let (resumed_chain, _) = chain(&blocks);
+
+        let rpc = MockRpc::start(move |method, params, _| {
+            if method == "eth_getLogs" {
+                scanned
+                    .lock()
+                    .unwrap()
+                    .push(mock_rpc::quantity(&params[0]["fromBlock"]));
+            }
+
+            resumed_chain.respond(method, params)
+        })
+        .await;
+
+        export(
+            args(&rpc, &resumed_dir, 1000),
+            false,
+            false,
+            hidden_progress(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(scanned_from.lock().unwrap().iter().min(), Some(&10));
+
+        let straight = std::fs::read(straight_dir.join("output.csv")).unwrap();
+
+        assert_eq!(
+            straight.iter().filter(|byte| **byte == b'\n').count(),
+            blocks.len() + 1
+        );
+        assert_eq!(
+            std::fs::read(resumed_dir.join("output.csv")).unwrap(),
+            straight
+        );
+        assert!(!Checkpoint::path(&resumed_dir.join("output.csv").display().to_string()).exists());
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[tokio::test]
+    async fn writes_the_received_leg_into_the_file_of_the_receiver() {
+        const COLD_WALLET: Address = address!("00000000000000000000000000000000000000bb");
+
+        // The wallet liquidates through the router, the vault shares going to the cold wallet.
+        let mut chain = MockChain::new(60).with_pair();
+        chain.transaction(
+            5,
+            WALLET,
+            vec![
+                (
+                    PRIZE_TOKEN,
+                    Transfer {
+                        from: WALLET,
+                        to: PAIR,
+                        value: U256::from(10),
+                    }
+                    .encode_log_data(),
+                ),
+                (
+                    VAULT,
+                    Transfer {
+                        from: PAIR,
+                        to: COLD_WALLET,
+                        value: U256::from(1000),
+                    }
+                    .encode_log_data(),
+                ),
+                (
+                    ROUTER,
+                    SwappedExactAmountOut {
+                        liquidationPair: PAIR,
+                        sender: WALLET,
+                        receiver: COLD_WALLET,
+                        amountOut: U256::from(1000),
+                        amountInMax: U256::from(10),
+                        amountIn: U256::from(10),
+                        deadline: U256::ZERO,
+                    }
+                    .encode_log_data(),
+                ),
+            ],
+        );
+
+        let dir = std::env::temp_dir().join(format!("export-{}", rand::random::<u64>()));
+        let rpc = chain.start().await;
+
+        export(
+            ConfigArgs {
+                sender: Some(format!("hot={},cold={}", WALLET, COLD_WALLET)),
+                match_on: Some("either".to_string()),
+                wallet_output: Some("split".to_string()),
+                ..args(&rpc, &dir, 1000)
+            },
+            false,
+            false,
+            hidden_progress(),
+        )
+        .await
+        .unwrap();
+
+        let rows = |name: &str| {
+            let content =
+                std::fs::read_to_string(dir.join(format!("output-{}.csv", name))).unwrap();
+            content
+                .lines()
+                .skip(1)
+                .map(|line| {
+                    line.split(',')
+                        .skip(1)
+                        .take(6)
+                        .collect::<Vec<_>>()
+                        .join(",")
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // Rows without their date
+        assert_eq!(
+            rows("hot"),
+            vec!["0.000000000000000010,POOL,,,0.000021000000000000,ETH"]
+        );
+        assert_eq!(rows("cold"), vec![",,0.000000000000001000,przDAI,,"]);
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+}
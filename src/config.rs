@@ -1,52 +1,495 @@
+use crate::{
+    cli::ConfigArgs,
+    errors::ErrorPolicy,
+    prices::{
+        PriceConfig, PriceSource, DEFAULT_CHAINLINK_HEARTBEAT, DEFAULT_NET_WORTH_CURRENCY,
+        DEFAULT_TWAP_SECONDS,
+    },
+    registry::{ChainRegistry, Registry},
+    retry::RetryConfig,
+    scanner::ChunkConfig,
+    wallets::{parse_wallets, ExportFile, ExportMode, MatchOn, Wallet, WalletOutput},
+};
+
 use alloy::primitives::{Address, U64};
-use dotenv::dotenv;
+use chrono::{DateTime, Months, NaiveDate};
 use eyre::{eyre, Result};
 use reqwest::Url;
-use std::env;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Default directory in which data resolved on-chain is cached
+pub const DEFAULT_CACHE_DIR: &str = "./cache";
+
+/// Default maximum number of logs processed concurrently
+pub const DEFAULT_CONCURRENCY: usize = 8;
+
+/// Default path of the Koinly CSV file, formatted with the start date
+pub const DEFAULT_OUTPUT: &str = "./results/%Y-%m.csv";
 
 #[derive(Clone)]
 pub struct Config {
     pub http_rpc: Url,
+    pub retry: RetryConfig,
+    pub chunks: ChunkConfig,
+    pub concurrency: usize,
     pub chain_id: U64,
-    pub sender: Address,
+    pub senders: Vec<Wallet>,
+    pub wallet_output: WalletOutput,
+    pub match_on: MatchOn,
+    pub export_mode: ExportMode,
+    pub vaults: Vec<Address>,
     pub start_timestamp: u64,
     pub end_timestamp: u64,
+    pub liquidation_router: Address,
+    pub prize_token: Address,
+    pub prize_pool: Option<Address>,
+    pub draw_manager: Option<Address>,
+    pub rng_relay_auction: Option<Address>,
+    pub registry: ChainRegistry,
+    pub record_reverted: bool,
+    pub net_transfers: bool,
+    pub prices: PriceConfig,
+    pub etherscan_api_key: Option<String>,
+    pub cache_dir: PathBuf,
+    pub on_error: ErrorPolicy,
+    pub output: String,
 }
 
 impl Config {
-    pub async fn read_from_dotenv() -> Result<Self> {
-        dotenv().ok();
+    /// Build the config from the command line arguments and environment variables,
+    /// falling back to the TOML config file for the options that are not set.
+    pub fn from_args(args: ConfigArgs) -> Result<Self> {
+        let args = args.with_config_file()?;
+        let (start_timestamp, end_timestamp) = args.timestamps()?;
+        let chain_id = args.chain_id()?;
+        let registry = args.registry()?.chain(chain_id)?.clone();
+
+        Ok(Self {
+            http_rpc: args.http_rpc()?,
+            retry: args.retry()?,
+            chunks: args.chunks()?,
+            concurrency: args.concurrency()?,
+            chain_id,
+            senders: args.senders()?,
+            wallet_output: args.wallet_output()?,
+            match_on: args.match_on()?,
+            export_mode: args.export_mode()?,
+            vaults: args.vaults()?,
+            start_timestamp,
+            end_timestamp,
+            liquidation_router: args.liquidation_router(&registry)?,
+            prize_token: args.prize_token(&registry)?,
+            prize_pool: args.prize_pool(&registry)?,
+            draw_manager: args.draw_manager(&registry)?,
+            rng_relay_auction: args.rng_relay_auction(&registry)?,
+            registry,
+            record_reverted: args.record_reverted()?,
+            net_transfers: args.net_transfers()?,
+            prices: args.prices()?,
+            etherscan_api_key: args.etherscan_api_key(),
+            cache_dir: args.cache_dir(),
+            on_error: args.on_error()?,
+            output: args.output(),
+        })
+    }
+
+    /// Path of the Koinly CSV file for the configured start date
+    pub fn output_path(&self) -> Result<String> {
+        format_output_path(&self.output, self.start_timestamp)
+    }
+
+    /// Koinly CSV files written for the configured senders
+    pub fn export_files(&self) -> Result<Vec<ExportFile>> {
+        Ok(ExportFile::for_wallets(
+            &self.output_path()?,
+            &self.senders,
+            self.wallet_output,
+        ))
+    }
+}
 
-        let get_env = |var| {
-            env::var(var).map_err(|_| eyre!("Required environment variable \"{}\" not set", var))
-        };
+/// Parsing of the individual options, so that subcommands only require the options they use
+impl ConfigArgs {
+    /// Fill the options that are not set with the ones from the TOML config file, if any
+    pub fn with_config_file(self) -> Result<Self> {
+        match self.config.clone() {
+            Some(path) => Ok(self.or(read_config_file(&path)?)),
+            None => Ok(self),
+        }
+    }
 
-        let http_rpc = get_env("HTTP_RPC")?
+    pub fn http_rpc(&self) -> Result<Url> {
+        get_arg(&self.http_rpc, "HTTP_RPC")?
             .parse()
-            .map_err(|_| eyre!("Failed to parse \"HTTP_RPC\""))?;
+            .map_err(|_| eyre!("Failed to parse \"HTTP_RPC\""))
+    }
+
+    /// Retry and rate limiting settings of the RPC requests, defaults to `RetryConfig::default()`
+    pub fn retry(&self) -> Result<RetryConfig> {
+        let default = RetryConfig::default();
+
+        let max_attempts = parse_optional_arg(&self.rpc_max_attempts, "RPC_MAX_ATTEMPTS")?
+            .unwrap_or(default.max_attempts);
+
+        if max_attempts == 0 {
+            return Err(eyre!("\"RPC_MAX_ATTEMPTS\" must be at least 1"));
+        }
+
+        let requests_per_second =
+            parse_optional_arg(&self.rpc_requests_per_second, "RPC_REQUESTS_PER_SECOND")?;
+
+        if requests_per_second == Some(0) {
+            return Err(eyre!("\"RPC_REQUESTS_PER_SECOND\" must be at least 1"));
+        }
+
+        Ok(RetryConfig {
+            max_attempts,
+            initial_backoff: parse_optional_arg(
+                &self.rpc_initial_backoff_ms,
+                "RPC_INITIAL_BACKOFF_MS",
+            )?
+            .map_or(default.initial_backoff, Duration::from_millis),
+            max_backoff: parse_optional_arg(&self.rpc_max_backoff_ms, "RPC_MAX_BACKOFF_MS")?
+                .map_or(default.max_backoff, Duration::from_millis),
+            requests_per_second,
+        })
+    }
+
+    /// Bounds of the number of blocks queried at once for logs, defaults to `ChunkConfig::default()`
+    pub fn chunks(&self) -> Result<ChunkConfig> {
+        let default = ChunkConfig::default();
+
+        let min_chunk_size = parse_optional_arg(&self.min_block_range, "MIN_BLOCK_RANGE")?
+            .unwrap_or(default.min_chunk_size);
+        let max_chunk_size = parse_optional_arg(&self.max_block_range, "MAX_BLOCK_RANGE")?
+            .unwrap_or(default.max_chunk_size);
 
-        let chain_id = get_env("CHAIN_ID")?
+        if min_chunk_size == 0 || min_chunk_size > max_chunk_size {
+            return Err(eyre!(
+                "\"MIN_BLOCK_RANGE\" must be at least 1 and at most \"MAX_BLOCK_RANGE\""
+            ));
+        }
+
+        Ok(ChunkConfig {
+            min_chunk_size,
+            max_chunk_size,
+        })
+    }
+
+    pub fn concurrency(&self) -> Result<usize> {
+        let concurrency =
+            parse_optional_arg(&self.concurrency, "CONCURRENCY")?.unwrap_or(DEFAULT_CONCURRENCY);
+
+        if concurrency == 0 {
+            return Err(eyre!("\"CONCURRENCY\" must be at least 1"));
+        }
+
+        Ok(concurrency)
+    }
+
+    pub fn chain_id(&self) -> Result<U64> {
+        get_arg(&self.chain_id, "CHAIN_ID")?
             .parse::<U64>()
-            .map_err(|_| eyre!("Failed to parse \"CHAIN_ID\""))?;
+            .map_err(|_| eyre!("Failed to parse \"CHAIN_ID\""))
+    }
+
+    /// Comma-separated list of senders, each optionally labeled with `label=address`
+    pub fn senders(&self) -> Result<Vec<Wallet>> {
+        parse_wallets(get_arg(&self.sender, "SENDER_ADDRESS")?)
+            .map_err(|error| eyre!("Failed to parse \"SENDER_ADDRESS\": {}", error))
+    }
+
+    pub fn wallet_output(&self) -> Result<WalletOutput> {
+        self.wallet_output
+            .as_deref()
+            .map_or(Ok(WalletOutput::default()), str::parse)
+    }
+
+    pub fn match_on(&self) -> Result<MatchOn> {
+        self.match_on
+            .as_deref()
+            .map_or(Ok(MatchOn::default()), str::parse)
+    }
+
+    pub fn export_mode(&self) -> Result<ExportMode> {
+        self.export_mode
+            .as_deref()
+            .map_or(Ok(ExportMode::default()), str::parse)
+    }
 
-        let sender = get_env("SENDER_ADDRESS")?
-            .parse::<Address>()
-            .map_err(|_| eyre!("Failed to parse \"SENDER_ADDRESS\""))?;
+    /// Vaults whose deposits and withdrawals are exported in depositor mode, the prize vaults of the known pairs if empty
+    pub fn vaults(&self) -> Result<Vec<Address>> {
+        self.vaults
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .filter(|vault| !vault.trim().is_empty())
+            .map(|vault| {
+                vault
+                    .trim()
+                    .parse::<Address>()
+                    .map_err(|_| eyre!("Failed to parse vault address \"{}\"", vault.trim()))
+            })
+            .collect()
+    }
 
-        let start_timestamp = get_env("START_TIMESTAMP")?
+    /// Start and end timestamps, taken from `MONTH` if set
+    pub fn timestamps(&self) -> Result<(u64, u64)> {
+        if let Some(month) = &self.month {
+            return parse_month(month);
+        }
+
+        let start_timestamp = get_arg(&self.start_timestamp, "START_TIMESTAMP")?
             .parse::<u64>()
             .map_err(|_| eyre!("Failed to parse \"START_TIMESTAMP\""))?;
 
-        let end_timestamp = get_env("END_TIMESTAMP")?
+        let end_timestamp = get_arg(&self.end_timestamp, "END_TIMESTAMP")?
             .parse::<u64>()
             .map_err(|_| eyre!("Failed to parse \"END_TIMESTAMP\""))?;
 
-        Ok(Self {
-            http_rpc,
-            chain_id,
-            sender,
-            start_timestamp,
-            end_timestamp,
+        Ok((start_timestamp, end_timestamp))
+    }
+
+    /// Registry file if set, default registry otherwise
+    pub fn registry(&self) -> Result<Registry> {
+        Registry::load(self.registry.as_ref().map(Path::new))
+    }
+
+    /// Liquidation router address, defaults to the one in the registry
+    pub fn liquidation_router(&self, registry: &ChainRegistry) -> Result<Address> {
+        match &self.liquidation_router {
+            Some(address) => address
+                .parse::<Address>()
+                .map_err(|_| eyre!("Failed to parse \"LIQUIDATION_ROUTER\"")),
+            None => registry
+                .get_liquidation_router_address()
+                .map_err(|error| eyre!("{}, set \"LIQUIDATION_ROUTER\"", error)),
+        }
+    }
+
+    /// Liquidation pair factory address, defaults to the one in the registry
+    pub fn liquidation_pair_factory(&self, registry: &ChainRegistry) -> Result<Option<Address>> {
+        match &self.liquidation_pair_factory {
+            Some(address) => address
+                .parse::<Address>()
+                .map(Some)
+                .map_err(|_| eyre!("Failed to parse \"LIQUIDATION_PAIR_FACTORY\"")),
+            None => Ok(registry.liquidation_pair_factory),
+        }
+    }
+
+    /// Prize token address, defaults to the one in the registry
+    pub fn prize_token(&self, registry: &ChainRegistry) -> Result<Address> {
+        match &self.prize_token {
+            Some(address) => address
+                .parse::<Address>()
+                .map_err(|_| eyre!("Failed to parse \"PRIZE_TOKEN\"")),
+            None => Ok(registry.prize_token),
+        }
+    }
+
+    /// Prize pool address, defaults to the one in the registry
+    pub fn prize_pool(&self, registry: &ChainRegistry) -> Result<Option<Address>> {
+        match &self.prize_pool {
+            Some(address) => address
+                .parse::<Address>()
+                .map(Some)
+                .map_err(|_| eyre!("Failed to parse \"PRIZE_POOL\"")),
+            None => Ok(registry.prize_pool),
+        }
+    }
+
+    /// Draw manager address, defaults to the one in the registry
+    pub fn draw_manager(&self, registry: &ChainRegistry) -> Result<Option<Address>> {
+        match &self.draw_manager {
+            Some(address) => address
+                .parse::<Address>()
+                .map(Some)
+                .map_err(|_| eyre!("Failed to parse \"DRAW_MANAGER\"")),
+            None => Ok(registry.draw_manager),
+        }
+    }
+
+    /// RNG relay auction address, defaults to the one in the registry
+    pub fn rng_relay_auction(&self, registry: &ChainRegistry) -> Result<Option<Address>> {
+        match &self.rng_relay_auction {
+            Some(address) => address
+                .parse::<Address>()
+                .map(Some)
+                .map_err(|_| eyre!("Failed to parse \"RNG_RELAY_AUCTION\"")),
+            None => Ok(registry.rng_relay_auction),
+        }
+    }
+
+    /// Whether to record reverted transactions, which are listed with Etherscan
+    pub fn record_reverted(&self) -> Result<bool> {
+        let record_reverted =
+            parse_optional_arg(&self.record_reverted, "RECORD_REVERTED")?.unwrap_or_default();
+
+        if record_reverted && self.etherscan_api_key().is_none() {
+            return Err(eyre!("\"RECORD_REVERTED\" requires \"ETHERSCAN_API_KEY\""));
+        }
+
+        if record_reverted && self.export_mode()? == ExportMode::Depositor {
+            return Err(eyre!(
+                "\"RECORD_REVERTED\" is not supported with \"EXPORT_MODE\" set to \"depositor\""
+            ));
+        }
+
+        Ok(record_reverted)
+    }
+
+    /// Whether to record liquidations as the net transfers of their transaction, false by default
+    pub fn net_transfers(&self) -> Result<bool> {
+        Ok(parse_optional_arg(&self.net_transfers, "NET_TRANSFERS")?.unwrap_or_default())
+    }
+
+    /// Sources of the prices used to value the rows, none by default
+    pub fn prices(&self) -> Result<PriceConfig> {
+        let sources = self
+            .price_sources
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .filter(|source| !source.trim().is_empty())
+            .map(|source| source.trim().parse())
+            .collect::<Result<Vec<PriceSource>>>()?;
+
+        let file = self.prices_file.as_ref().map(PathBuf::from);
+
+        if sources.contains(&PriceSource::Csv) && file.is_none() {
+            return Err(eyre!("The \"csv\" price source requires \"PRICES_FILE\""));
+        }
+
+        let twap_seconds =
+            parse_optional_arg(&self.twap_seconds, "TWAP_SECONDS")?.unwrap_or(DEFAULT_TWAP_SECONDS);
+
+        if twap_seconds == 0 {
+            return Err(eyre!("\"TWAP_SECONDS\" must be at least 1"));
+        }
+
+        Ok(PriceConfig {
+            sources,
+            file,
+            currency: self
+                .net_worth_currency
+                .clone()
+                .unwrap_or_else(|| DEFAULT_NET_WORTH_CURRENCY.to_string()),
+            twap_seconds,
+            chainlink_heartbeat: parse_optional_arg(
+                &self.chainlink_heartbeat,
+                "CHAINLINK_HEARTBEAT",
+            )?
+            .unwrap_or(DEFAULT_CHAINLINK_HEARTBEAT),
+        })
+    }
+
+    pub fn etherscan_api_key(&self) -> Option<String> {
+        self.etherscan_api_key
+            .clone()
+            .filter(|api_key| !api_key.is_empty())
+    }
+
+    pub fn cache_dir(&self) -> PathBuf {
+        PathBuf::from(
+            self.cache_dir
+                .clone()
+                .unwrap_or_else(|| DEFAULT_CACHE_DIR.to_string()),
+        )
+    }
+
+    pub fn on_error(&self) -> Result<ErrorPolicy> {
+        self.on_error
+            .as_deref()
+            .map_or(Ok(ErrorPolicy::default()), str::parse)
+    }
+
+    pub fn output(&self) -> String {
+        self.output
+            .clone()
+            .unwrap_or_else(|| DEFAULT_OUTPUT.to_string())
+    }
+
+    /// Path of the Koinly CSV file for the configured start date
+    pub fn output_path(&self) -> Result<String> {
+        format_output_path(&self.output(), self.timestamps()?.0)
+    }
+}
+
+fn get_arg<'a>(value: &'a Option<String>, var: &str) -> Result<&'a String> {
+    value
+        .as_ref()
+        .ok_or_else(|| eyre!("Required option \"{}\" not set", var))
+}
+
+fn parse_optional_arg<V: FromStr>(value: &Option<String>, var: &str) -> Result<Option<V>> {
+    value
+        .as_ref()
+        .map(|value| {
+            value
+                .parse::<V>()
+                .map_err(|_| eyre!("Failed to parse \"{}\"", var))
         })
+        .transpose()
+}
+
+fn format_output_path(output: &str, start_timestamp: u64) -> Result<String> {
+    let date = DateTime::from_timestamp(start_timestamp as i64, 0)
+        .ok_or_else(|| eyre!("Invalid start timestamp {}", start_timestamp))?;
+
+    // Formatting panics on invalid specifiers when converted with `to_string`, so the error of
+    // `write!` is returned instead.
+    let mut path = String::new();
+    write!(path, "{}", date.format(output)).map_err(|_| eyre!("Failed to parse \"OUTPUT\""))?;
+
+    Ok(path)
+}
+
+fn read_config_file(path: &Path) -> Result<ConfigArgs> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|error| eyre!("Failed to read config file {}: {}", path.display(), error))?;
+
+    toml::from_str(&content)
+        .map_err(|error| eyre!("Failed to parse config file {}: {}", path.display(), error))
+}
+
+/// Get the start and end timestamps of a `YYYY-MM` month in UTC
+fn parse_month(month: &str) -> Result<(u64, u64)> {
+    let parse_error = || eyre!("Failed to parse \"MONTH\", expected YYYY-MM");
+
+    let start = NaiveDate::parse_from_str(&format!("{}-01", month), "%Y-%m-%d")
+        .map_err(|_| parse_error())?;
+    let end = start
+        .checked_add_months(Months::new(1))
+        .ok_or_else(parse_error)?;
+
+    let to_timestamp = |date: NaiveDate| {
+        date.and_hms_opt(0, 0, 0)
+            .map(|datetime| datetime.and_utc().timestamp() as u64)
+            .ok_or_else(parse_error)
+    };
+
+    Ok((to_timestamp(start)?, to_timestamp(end)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_output_path_with_start_date() {
+        assert_eq!(
+            format_output_path(DEFAULT_OUTPUT, 1_722_470_400).unwrap(),
+            "./results/2024-08.csv"
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_output_specifiers() {
+        assert!(format_output_path("./results/%.csv", 1_722_470_400).is_err());
+        assert!(format_output_path("./results/%Q.csv", 1_722_470_400).is_err());
     }
 }
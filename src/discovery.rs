@@ -0,0 +1,705 @@
+use crate::{
+    cache::{Cache, CacheKind},
+    helpers::{push_unique, save_cache},
+    log_info_cyan,
+    registry::ChainRegistry,
+    scanner::{ChunkConfig, LogScanner},
+};
+
+use alloy::{
+    network::AnyNetwork,
+    primitives::Address,
+    providers::Provider,
+    rpc::types::Filter,
+    sol,
+    sol_types::SolEvent,
+    transports::{RpcError, Transport},
+};
+use colored::Colorize;
+use eyre::{eyre, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+sol! {
+    #[sol(rpc)]
+    interface ILiquidationPair {
+        function source() external view returns (address);
+        function tokenOut() external view returns (address);
+    }
+
+    #[sol(rpc)]
+    interface IERC4626 {
+        function asset() external view returns (address);
+    }
+
+    /// Factory of the CGDA liquidation pairs, e.g. the Optimism Canary deployment
+    interface CgdaLiquidationPairFactory {
+        event PairCreated(
+            address indexed pair,
+            address source,
+            address indexed tokenIn,
+            address indexed tokenOut,
+            uint32 periodLength,
+            uint32 periodOffset,
+            uint32 targetFirstSaleTime,
+            int256 decayConstant,
+            uint112 initialAmountIn,
+            uint112 initialAmountOut,
+            uint256 minimumAuctionAmount
+        );
+    }
+
+    /// Factory of the TPDA liquidation pairs
+    interface TpdaLiquidationPairFactory {
+        event PairCreated(
+            address indexed pair,
+            address source,
+            address indexed tokenIn,
+            address indexed tokenOut,
+            uint64 targetAuctionPeriod,
+            uint192 targetAuctionPrice,
+            uint256 smoothingFactor
+        );
+    }
+}
+
+/// Liquidation pair resolved on-chain
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DiscoveredPair {
+    /// Address of the liquidation pair
+    pub address: Address,
+    /// Liquidation source of the pair, i.e. the vault
+    pub source: Address,
+    /// Token sent by the pair when liquidating
+    pub token_out: Address,
+    /// Underlying asset of the vault
+    pub underlying_asset: Address,
+}
+
+/// Liquidation pairs resolved on-chain, persisted between runs
+#[derive(Default, Serialize, Deserialize)]
+struct PairCache {
+    /// Last block scanned for `PairCreated` events, keyed by liquidation pair factory
+    #[serde(default)]
+    last_scanned_blocks: BTreeMap<Address, u64>,
+    /// Resolved liquidation pairs
+    liquidation_pairs: Vec<DiscoveredPair>,
+    /// Underlying assets of the vaults resolved on-chain, keyed by vault
+    #[serde(default)]
+    vault_assets: BTreeMap<Address, Address>,
+}
+
+/// Resolves the underlying asset of liquidation pairs, looking them up in the registry first
+/// and falling back to on-chain calls for unknown pairs. Pairs resolved on-chain are cached on disk.
+pub struct PairResolver<T, P> {
+    /// Provider
+    provider: Arc<P>,
+    /// Registry of the chain
+    registry: ChainRegistry,
+    /// Path of the cache file
+    cache_path: PathBuf,
+    /// Pairs resolved on-chain
+    cache: Mutex<PairCache>,
+    phantom: PhantomData<T>,
+}
+
+impl<T, P> PairResolver<T, P>
+where
+    T: Transport + Clone,
+    P: Provider<T, AnyNetwork>,
+{
+    pub fn new(provider: Arc<P>, registry: ChainRegistry, cache_dir: &Path) -> Result<Self> {
+        let cache_path = Cache::resolved_path(cache_dir, registry.chain_id, CacheKind::Pairs);
+
+        let cache = match std::fs::read_to_string(&cache_path) {
+            Ok(content) => serde_json::from_str(&content).map_err(|error| {
+                eyre!("Failed to parse cache {}: {}", cache_path.display(), error)
+            })?,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => PairCache::default(),
+            Err(error) => {
+                return Err(eyre!(
+                    "Failed to read cache {}: {}",
+                    cache_path.display(),
+                    error
+                ))
+            }
+        };
+
+        Ok(Self {
+            provider,
+            registry,
+            cache_path,
+            cache: Mutex::new(cache),
+            phantom: PhantomData,
+        })
+    }
+
+    /// Get the underlying asset address for a given liquidation pair
+    pub async fn get_underlying_asset_address(&self, liquidation_pair: Address) -> Result<Address> {
+        if let Ok(underlying_asset) = self.registry.get_underlying_asset_address(liquidation_pair) {
+            return Ok(underlying_asset);
+        }
+
+        if let Some(pair) = self.get_cached_pair(liquidation_pair) {
+            return Ok(pair.underlying_asset);
+        }
+
+        Ok(self.resolve_pair(liquidation_pair).await?.underlying_asset)
+    }
+
+    /// Get the token sent by a given liquidation pair to the receiver of its swaps, e.g. the vault shares
+    pub async fn get_token_out_address(&self, liquidation_pair: Address) -> Result<Address> {
+        if let Some(pair) = self.get_cached_pair(liquidation_pair) {
+            return Ok(pair.token_out);
+        }
+
+        Ok(self.resolve_pair(liquidation_pair).await?.token_out)
+    }
+
+    /// Get the underlying asset of a given ERC-4626 vault, looking it up in the liquidation pairs
+    /// resolved so far before reading its `asset()` on-chain
+    pub async fn get_vault_asset_address(&self, vault: Address) -> Result<Address> {
+        {
+            let cache = self.cache.lock().unwrap();
+
+            if let Some(asset) = cache.vault_assets.get(&vault).copied().or_else(|| {
+                cache
+                    .liquidation_pairs
+                    .iter()
+                    .find(|pair| pair.source == vault)
+                    .map(|pair| pair.underlying_asset)
+            }) {
+                return Ok(asset);
+            }
+        }
+
+        let asset = IERC4626::new(vault, self.provider.as_ref())
+            .asset()
+            .call()
+            .await
+            .map_err(|error| eyre!("Failed to read the asset of vault {}: {}", vault, error))?
+            ._0;
+
+        log_info_cyan!("Resolved vault {} with underlying asset {}", vault, asset);
+
+        self.cache.lock().unwrap().vault_assets.insert(vault, asset);
+        save_cache(&self.cache_path, &self.cache)?;
+
+        Ok(asset)
+    }
+
+    /// Liquidation pairs resolved on-chain so far
+    pub fn discovered_pairs(&self) -> Vec<DiscoveredPair> {
+        self.cache.lock().unwrap().liquidation_pairs.clone()
+    }
+
+    /// Addresses of the liquidation pairs of the registry and resolved on-chain so far
+    pub fn known_pair_addresses(&self) -> Vec<Address> {
+        let mut addresses: Vec<_> = self
+            .registry
+            .liquidation_pairs
+            .iter()
+            .map(|pair| pair.address)
+            .collect();
+
+        for pair in self.cache.lock().unwrap().liquidation_pairs.iter() {
+            push_unique(&mut addresses, pair.address, |address| *address);
+        }
+
+        addresses
+    }
+
+    /// Whether a liquidation pair is in the registry or was resolved on-chain,
+    /// e.g. discovered from the factory
+    pub fn is_known_pair(&self, liquidation_pair: Address) -> bool {
+        self.registry
+            .get_underlying_asset_address(liquidation_pair)
+            .is_ok()
+            || self.get_cached_pair(liquidation_pair).is_some()
+    }
+
+    /// Liquidation pairs of the registry and resolved on-chain so far, along with their source vault.
+    /// Pairs of the registry are resolved on-chain the first time, those failing to resolve are skipped.
+    pub async fn known_pairs(&self) -> Vec<DiscoveredPair> {
+        for pair in &self.registry.liquidation_pairs {
+            if self.get_cached_pair(pair.address).is_none() {
+                if let Err(error) = self.resolve_pair(pair.address).await {
+                    log::warn!("Skipping liquidation pair {}: {}", pair.address, error);
+                }
+            }
+        }
+
+        self.discovered_pairs()
+    }
+
+    /// Scan `PairCreated` events of the liquidation pair factory up to `to_block`
+    /// and resolve the pairs that are not known yet.
+    /// Scanning starts from `from_block` if given, otherwise it resumes from the last block
+    /// scanned for this factory by a previous run, or starts from `start_block`.
+    /// Blocks are queried by chunks whose size adapts to the limits of the provider.
+    pub async fn discover(
+        &self,
+        factory: Address,
+        from_block: Option<u64>,
+        start_block: u64,
+        to_block: u64,
+        cache: Arc<Cache>,
+        chunks: ChunkConfig,
+    ) -> Result<()> {
+        let last_scanned_block = self
+            .cache
+            .lock()
+            .unwrap()
+            .last_scanned_blocks
+            .get(&factory)
+            .copied();
+        let from_block = match (from_block, last_scanned_block) {
+            (Some(from_block), _) => from_block,
+            (None, Some(last_scanned_block)) => last_scanned_block + 1,
+            (None, None) => start_block,
+        };
+
+        log_info_cyan!(
+            "Discovering liquidation pairs created by {} from block {} to {}",
+            factory,
+            from_block,
+            to_block
+        );
+
+        // The `PairCreated` event layout differs between factory versions,
+        // but all of them index the created pair as the first topic.
+        let filter = Filter::new().address(factory).event_signature(vec![
+            CgdaLiquidationPairFactory::PairCreated::SIGNATURE_HASH,
+            TpdaLiquidationPairFactory::PairCreated::SIGNATURE_HASH,
+        ]);
+
+        let mut scanner = LogScanner::new(
+            self.provider.clone(),
+            vec![filter],
+            cache,
+            from_block,
+            to_block,
+            chunks,
+        );
+
+        while let Some(chunk) = scanner.next_chunk().await? {
+            for log in chunk.logs {
+                let Some(topic) = log.topics().get(1) else {
+                    continue;
+                };
+
+                let liquidation_pair = Address::from_word(*topic);
+
+                if self
+                    .registry
+                    .get_underlying_asset_address(liquidation_pair)
+                    .is_err()
+                    && self.get_cached_pair(liquidation_pair).is_none()
+                {
+                    if let Err(error) = self.resolve_pair(liquidation_pair).await {
+                        log::warn!(
+                            "Skipping liquidation pair {} created by {}: {}",
+                            liquidation_pair,
+                            factory,
+                            error
+                        );
+                    }
+                }
+            }
+
+            self.cache
+                .lock()
+                .unwrap()
+                .last_scanned_blocks
+                .insert(factory, chunk.to_block);
+            save_cache(&self.cache_path, &self.cache)?;
+        }
+
+        scanner.stats.report();
+
+        Ok(())
+    }
+
+    fn get_cached_pair(&self, liquidation_pair: Address) -> Option<DiscoveredPair> {
+        self.cache
+            .lock()
+            .unwrap()
+            .liquidation_pairs
+            .iter()
+            .find(|pair| pair.address == liquidation_pair)
+            .cloned()
+    }
+
+    /// Read the source, token out and underlying asset of a liquidation pair on-chain
+    async fn resolve_pair(&self, liquidation_pair: Address) -> Result<DiscoveredPair> {
+        let pair_contract = ILiquidationPair::new(liquidation_pair, self.provider.as_ref());
+
+        let source = pair_contract.source().call().await?._0;
+        let token_out = pair_contract.tokenOut().call().await?._0;
+
+        // Sources that are not ERC-4626 vaults liquidate the token out directly. Other errors
+        // are returned so that a pair is never cached with the wrong asset.
+        let underlying_asset = match IERC4626::new(source, self.provider.as_ref())
+            .asset()
+            .call()
+            .await
+        {
+            Ok(asset) => asset._0,
+            Err(error) if is_revert(&error) => token_out,
+            Err(error) => {
+                return Err(eyre!(
+                    "Failed to read the asset of source {} of liquidation pair {}: {}",
+                    source,
+                    liquidation_pair,
+                    error
+                ))
+            }
+        };
+
+        let pair = DiscoveredPair {
+            address: liquidation_pair,
+            source,
+            token_out,
+            underlying_asset,
+        };
+
+        log_info_cyan!(
+            "Resolved liquidation pair {} with underlying asset {}",
+            liquidation_pair,
+            underlying_asset
+        );
+
+        push_unique(
+            &mut self.cache.lock().unwrap().liquidation_pairs,
+            pair.clone(),
+            |pair| pair.address,
+        );
+        save_cache(&self.cache_path, &self.cache)?;
+
+        Ok(pair)
+    }
+}
+
+/// Whether a call failed because the contract reverted or returned data that failed to decode,
+/// e.g. a source without an `asset()` function, rather than because of the provider
+fn is_revert(error: &alloy::contract::Error) -> bool {
+    match error {
+        alloy::contract::Error::AbiError(_) => true,
+        alloy::contract::Error::TransportError(RpcError::ErrorResp(payload)) => {
+            payload.message.to_lowercase().contains("revert")
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        mock_rpc::{MockResponse, MockRpc},
+        registry::Registry,
+    };
+
+    use alloy::{
+        hex,
+        primitives::{address, B256},
+        providers::{ProviderBuilder, RootProvider},
+        sol_types::SolCall,
+        transports::http::{Client as HttpClient, Http},
+    };
+    use serde_json::{json, Value};
+
+    const FACTORY: Address = address!("00000000000000000000000000000000000000fa");
+    const PAIR: Address = address!("00000000000000000000000000000000000000a1");
+    const BROKEN_PAIR: Address = address!("00000000000000000000000000000000000000a2");
+    const VAULT: Address = address!("00000000000000000000000000000000000000b1");
+    const ASSET: Address = address!("00000000000000000000000000000000000000c1");
+    const PLAIN_PAIR: Address = address!("00000000000000000000000000000000000000a3");
+    const FLAKY_PAIR: Address = address!("00000000000000000000000000000000000000a4");
+    const SOURCE: Address = address!("00000000000000000000000000000000000000d1");
+    const FLAKY_VAULT: Address = address!("00000000000000000000000000000000000000b2");
+
+    /// `PairCreated` log of the factory creating a given pair
+    fn pair_created(pair: Address, log_index: u64) -> Value {
+        json!({
+            "address": FACTORY,
+            "topics": [
+                CgdaLiquidationPairFactory::PairCreated::SIGNATURE_HASH,
+                pair.into_word(),
+                B256::ZERO,
+                B256::ZERO,
+            ],
+            "data": "0x",
+            "blockNumber": "0x1",
+            "blockHash": B256::ZERO,
+            "transactionHash": B256::ZERO,
+            "transactionIndex": "0x0",
+            "logIndex": format!("0x{:x}", log_index),
+            "removed": false
+        })
+    }
+
+    /// Cache that never stores the chunks of the scans
+    fn no_cache() -> Arc<Cache> {
+        Arc::new(Cache::new(Path::new("unused"), 10))
+    }
+
+    /// Answer the calls of the pairs and their sources: the broken pair and the source that is not a
+    /// vault revert, while the provider fails to read the asset of the flaky vault
+    fn call(params: &Value) -> MockResponse {
+        let to: Address = params[0]["to"].as_str().unwrap().parse().unwrap();
+        let input = hex::decode(
+            params[0]["input"]
+                .as_str()
+                .or(params[0]["data"].as_str())
+                .unwrap(),
+        )
+        .unwrap();
+        let word = |address: Address| MockResponse::Result(json!(address.into_word()));
+
+        match (to, &input[..4]) {
+            (PAIR, selector) if selector == ILiquidationPair::sourceCall::SELECTOR => word(VAULT),
+            (PAIR, selector) if selector == ILiquidationPair::tokenOutCall::SELECTOR => word(VAULT),
+            (VAULT, selector) if selector == IERC4626::assetCall::SELECTOR => word(ASSET),
+            (PLAIN_PAIR, selector) if selector == ILiquidationPair::sourceCall::SELECTOR => {
+                word(SOURCE)
+            }
+            (PLAIN_PAIR, selector) if selector == ILiquidationPair::tokenOutCall::SELECTOR => {
+                word(ASSET)
+            }
+            (FLAKY_PAIR, _) => word(FLAKY_VAULT),
+            (FLAKY_VAULT, _) => MockResponse::Status(500),
+            _ => MockResponse::Error(3, "execution reverted".to_string()),
+        }
+    }
+
+    #[tokio::test]
+    async fn skips_pairs_that_fail_to_resolve() {
+        let rpc = MockRpc::start(|method, params, _| match method {
+            "eth_getLogs" => {
+                let signatures: Vec<B256> =
+                    serde_json::from_value(params[0]["topics"][0].clone()).unwrap();
+
+                assert_eq!(signatures.len(), 2);
+                assert!(
+                    signatures.contains(&CgdaLiquidationPairFactory::PairCreated::SIGNATURE_HASH)
+                );
+                assert!(
+                    signatures.contains(&TpdaLiquidationPairFactory::PairCreated::SIGNATURE_HASH)
+                );
+
+                MockResponse::Result(json!([pair_created(BROKEN_PAIR, 0), pair_created(PAIR, 1)]))
+            }
+            "eth_call" => call(params),
+            _ => MockResponse::Error(-32601, format!("unsupported method {}", method)),
+        })
+        .await;
+
+        let provider: RootProvider<Http<HttpClient>, AnyNetwork> = ProviderBuilder::new()
+            .network::<AnyNetwork>()
+            .on_http(rpc.url.clone());
+        let registry = Registry::load(None).unwrap().chains[0].clone();
+        let cache_dir = std::env::temp_dir().join(format!("discovery-{}", rand::random::<u64>()));
+        let pairs = PairResolver::new(Arc::new(provider), registry, &cache_dir).unwrap();
+
+        pairs
+            .discover(FACTORY, None, 0, 0, no_cache(), ChunkConfig::default())
+            .await
+            .unwrap();
+
+        let discovered = pairs.discovered_pairs();
+
+        assert_eq!(discovered.len(), 1);
+        assert_eq!(discovered[0].address, PAIR);
+        assert_eq!(discovered[0].underlying_asset, ASSET);
+
+        // Only pairs that resolved are trusted, along with the pairs of the registry.
+        assert!(pairs.is_known_pair(PAIR));
+        assert!(!pairs.is_known_pair(BROKEN_PAIR));
+        assert!(pairs.known_pair_addresses().contains(&PAIR));
+        assert!(!pairs.known_pair_addresses().contains(&BROKEN_PAIR));
+
+        std::fs::remove_dir_all(cache_dir).ok();
+    }
+
+    #[tokio::test]
+    async fn only_falls_back_to_the_token_out_on_reverts() {
+        let rpc = MockRpc::start(|method, params, _| match method {
+            "eth_call" => call(params),
+            _ => MockResponse::Error(-32601, format!("unsupported method {}", method)),
+        })
+        .await;
+
+        let provider: RootProvider<Http<HttpClient>, AnyNetwork> = ProviderBuilder::new()
+            .network::<AnyNetwork>()
+            .on_http(rpc.url.clone());
+        let registry = Registry::load(None).unwrap().chains[0].clone();
+        let cache_dir = std::env::temp_dir().join(format!("discovery-{}", rand::random::<u64>()));
+        let pairs = PairResolver::new(Arc::new(provider), registry, &cache_dir).unwrap();
+
+        assert_eq!(
+            pairs
+                .get_underlying_asset_address(PLAIN_PAIR)
+                .await
+                .unwrap(),
+            ASSET
+        );
+        assert!(pairs
+            .get_underlying_asset_address(FLAKY_PAIR)
+            .await
+            .is_err());
+
+        let discovered = pairs.discovered_pairs();
+
+        assert_eq!(discovered.len(), 1);
+        assert_eq!(discovered[0].address, PLAIN_PAIR);
+
+        std::fs::remove_dir_all(cache_dir).ok();
+    }
+
+    #[test]
+    fn fails_on_unreadable_caches() {
+        let registry = Registry::load(None).unwrap().chains[0].clone();
+        let cache_dir = std::env::temp_dir().join(format!("discovery-{}", rand::random::<u64>()));
+        let cache_path = Cache::resolved_path(&cache_dir, registry.chain_id, CacheKind::Pairs);
+        let provider = || -> Arc<RootProvider<Http<HttpClient>, AnyNetwork>> {
+            Arc::new(
+                ProviderBuilder::new()
+                    .network::<AnyNetwork>()
+                    .on_http("http://127.0.0.1:1".parse().unwrap()),
+            )
+        };
+
+        assert!(PairResolver::new(provider(), registry.clone(), &cache_dir).is_ok());
+
+        std::fs::create_dir_all(&cache_path).unwrap();
+        assert!(PairResolver::new(provider(), registry.clone(), &cache_dir).is_err());
+
+        std::fs::remove_dir(&cache_path).unwrap();
+        std::fs::write(&cache_path, "{").unwrap();
+        assert!(PairResolver::new(provider(), registry, &cache_dir).is_err());
+
+        std::fs::remove_dir_all(cache_dir).ok();
+    }
+
+    #[tokio::test]
+    async fn shrinks_ranges_rejected_by_the_provider() {
+        let ranges = Arc::new(Mutex::new(Vec::new()));
+        let scanned = ranges.clone();
+
+        let rpc = MockRpc::start(move |method, params, _| match method {
+            "eth_getLogs" => {
+                let from_block = crate::mock_rpc::quantity(&params[0]["fromBlock"]);
+                let to_block = crate::mock_rpc::quantity(&params[0]["toBlock"]);
+
+                if to_block - from_block >= 500 {
+                    return MockResponse::Error(-32000, "block range too large".to_string());
+                }
+
+                scanned.lock().unwrap().push((from_block, to_block));
+                MockResponse::Result(json!([]))
+            }
+            _ => MockResponse::Error(-32601, format!("unsupported method {}", method)),
+        })
+        .await;
+
+        let provider: RootProvider<Http<HttpClient>, AnyNetwork> = ProviderBuilder::new()
+            .network::<AnyNetwork>()
+            .on_http(rpc.url.clone());
+        let registry = Registry::load(None).unwrap().chains[0].clone();
+        let cache_dir = std::env::temp_dir().join(format!("discovery-{}", rand::random::<u64>()));
+        let pairs = PairResolver::new(Arc::new(provider), registry, &cache_dir).unwrap();
+
+        pairs
+            .discover(FACTORY, None, 0, 1999, no_cache(), ChunkConfig::default())
+            .await
+            .unwrap();
+
+        let ranges = ranges.lock().unwrap();
+
+        assert_eq!(ranges.first(), Some(&(0, 499)));
+        assert_eq!(ranges.last().map(|range| range.1), Some(1999));
+        assert!(ranges.iter().all(|(from, to)| to - from < 500));
+        assert_eq!(
+            pairs
+                .cache
+                .lock()
+                .unwrap()
+                .last_scanned_blocks
+                .get(&FACTORY),
+            Some(&1999)
+        );
+
+        std::fs::remove_dir_all(cache_dir).ok();
+    }
+
+    #[tokio::test]
+    async fn resumes_scans_per_factory() {
+        let scanned_from = Arc::new(Mutex::new(Vec::new()));
+        let scanned = scanned_from.clone();
+
+        let rpc = MockRpc::start(move |method, params, _| match method {
+            "eth_getLogs" => {
+                scanned.lock().unwrap().push((
+                    params[0]["address"]
+                        .as_str()
+                        .unwrap()
+                        .parse::<Address>()
+                        .unwrap(),
+                    crate::mock_rpc::quantity(&params[0]["fromBlock"]),
+                ));
+
+                MockResponse::Result(json!([]))
+            }
+            _ => MockResponse::Error(-32601, format!("unsupported method {}", method)),
+        })
+        .await;
+
+        let provider: RootProvider<Http<HttpClient>, AnyNetwork> = ProviderBuilder::new()
+            .network::<AnyNetwork>()
+            .on_http(rpc.url.clone());
+        let registry = Registry::load(None).unwrap().chains[0].clone();
+        let cache_dir = std::env::temp_dir().join(format!("discovery-{}", rand::random::<u64>()));
+        let pairs = PairResolver::new(Arc::new(provider), registry, &cache_dir).unwrap();
+        let other_factory = address!("00000000000000000000000000000000000000fb");
+
+        pairs
+            .discover(FACTORY, None, 0, 10, no_cache(), ChunkConfig::default())
+            .await
+            .unwrap();
+        pairs
+            .discover(FACTORY, None, 0, 20, no_cache(), ChunkConfig::default())
+            .await
+            .unwrap();
+        pairs
+            .discover(
+                other_factory,
+                None,
+                5,
+                20,
+                no_cache(),
+                ChunkConfig::default(),
+            )
+            .await
+            .unwrap();
+        pairs
+            .discover(FACTORY, Some(3), 0, 20, no_cache(), ChunkConfig::default())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            *scanned_from.lock().unwrap(),
+            vec![
+                (FACTORY, 0),
+                (FACTORY, 11),
+                (other_factory, 5),
+                (FACTORY, 3)
+            ]
+        );
+
+        std::fs::remove_dir_all(cache_dir).ok();
+    }
+}
@@ -0,0 +1,115 @@
+use crate::transfers::TransferMismatch;
+
+use alloy::{
+    primitives::{Address, TxHash},
+    transports::TransportError,
+};
+use eyre::{eyre, Result};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use thiserror::Error;
+
+/// Errors raised while decoding a single log
+#[derive(Debug, Error)]
+pub enum BotError {
+    #[error("RPC request failed: {0}")]
+    Rpc(#[from] TransportError),
+    #[error("Log is missing its {0}")]
+    MissingLogField(&'static str),
+    #[error("Block {0} not found")]
+    MissingBlock(u64),
+    #[error("Receipt not found for transaction {0}")]
+    MissingReceipt(TxHash),
+    #[error("Failed to resolve liquidation pair {pair}: {reason}")]
+    UnknownPair { pair: Address, reason: String },
+    #[error("Failed to resolve vault {vault}: {reason}")]
+    UnknownVault { vault: Address, reason: String },
+    #[error("Failed to resolve token {token}: {reason}")]
+    UnknownToken { token: Address, reason: String },
+    #[error("Failed to get the price of {currency}: {reason}")]
+    Price { currency: String, reason: String },
+    #[error("Failed to decode {0}")]
+    Decode(String),
+    #[error("L1 fee not found in receipt of transaction {0}")]
+    MissingL1Fee(TxHash),
+}
+
+/// What to do when a log fails to be decoded
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    /// Stop the export at the first failure
+    #[default]
+    Abort,
+    /// Record the failure in the report and keep going
+    Continue,
+}
+
+impl FromStr for ErrorPolicy {
+    type Err = eyre::Report;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value {
+            "abort" => Ok(Self::Abort),
+            "continue" => Ok(Self::Continue),
+            _ => Err(eyre!(
+                "Failed to parse \"ON_ERROR\", expected \"abort\" or \"continue\""
+            )),
+        }
+    }
+}
+
+/// Log that failed to be decoded
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LogFailure {
+    #[serde(rename = "Block")]
+    pub block_number: Option<u64>,
+    #[serde(rename = "Log Index")]
+    pub log_index: Option<u64>,
+    #[serde(rename = "TxHash")]
+    pub tx_hash: Option<TxHash>,
+    #[serde(rename = "Error")]
+    pub error: String,
+}
+
+/// Failures and transfer mismatches collected during an export
+#[derive(Default)]
+pub struct FailureReport {
+    pub failures: Vec<LogFailure>,
+    pub mismatches: Vec<TransferMismatch>,
+}
+
+impl FailureReport {
+    pub fn is_empty(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    pub fn push(&mut self, failure: LogFailure) {
+        self.failures.push(failure);
+    }
+
+    /// Write the failures to a CSV file
+    pub fn write_to_csv(&self, path: &str) -> Result<()> {
+        let mut wtr = csv::Writer::from_path(path)?;
+
+        for failure in &self.failures {
+            wtr.serialize(failure)?;
+        }
+
+        wtr.flush()?;
+
+        Ok(())
+    }
+
+    /// Write the transfer mismatches to a CSV file
+    pub fn write_mismatches_to_csv(&self, path: &str) -> Result<()> {
+        let mut wtr = csv::Writer::from_path(path)?;
+
+        for mismatch in &self.mismatches {
+            wtr.serialize(mismatch)?;
+        }
+
+        wtr.flush()?;
+
+        Ok(())
+    }
+}
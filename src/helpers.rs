@@ -1,3 +1,9 @@
+use eyre::{eyre, Result};
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+
 /// Logging macros
 #[macro_export]
 macro_rules! log_info_cyan {
@@ -5,3 +11,35 @@ macro_rules! log_info_cyan {
         log::info!("{}", format_args!($($arg)*).to_string().cyan());
     };
 }
+
+/// Write a value as JSON to a temporary file first, then move it to `path`,
+/// so that concurrent writers and interrupted runs never leave a partially written file behind
+pub fn write_json_atomically<V: Serialize + ?Sized>(path: &Path, value: &V) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let temporary_path = path.with_extension(format!("{:x}.tmp", rand::random::<u64>()));
+
+    fs::write(&temporary_path, serde_json::to_vec(value)?)?;
+    fs::rename(&temporary_path, path)?;
+
+    Ok(())
+}
+
+/// Persist a cache of data resolved on-chain to `path`.
+/// The lock is held while writing so that concurrent saves don't interleave.
+pub fn save_cache<V: Serialize>(path: &Path, cache: &Mutex<V>) -> Result<()> {
+    let cache = cache.lock().unwrap();
+
+    write_json_atomically(path, &*cache)
+        .map_err(|error| eyre!("Failed to write cache {}: {}", path.display(), error))
+}
+
+/// Push a value unless one with the same key is already in `values`.
+/// Caches of data resolved on-chain rely on it, since the same value may have been resolved concurrently by another log.
+pub fn push_unique<V, K: PartialEq>(values: &mut Vec<V>, value: V, key: impl Fn(&V) -> K) {
+    if !values.iter().any(|cached| key(cached) == key(&value)) {
+        values.push(value);
+    }
+}
@@ -27,7 +27,7 @@ r#"
 ╚═╝  ╚═╝ ╚═════╝ ╚═╝╚═╝  ╚═══╝╚══════╝╚═╝       ╚═╝  ╚═╝ ╚═════╝ ╚═════╝ ╚═════╝  ╚═════╝ ╚═╝  ╚═══╝   ╚═╝   ╚═╝╚═╝  ╚═══╝ ╚═════╝
 "#};
 
-    log::info!("{}", format!("{}", banner.green().bold()));
+    log::info!("{}", banner.green().bold());
 }
 
 pub fn setup_logger(multi: MultiProgress) -> Result<()> {
@@ -1,5 +1,24 @@
+pub mod append;
+pub mod blocks;
 pub mod bot;
+pub mod cache;
+pub mod checkpoint;
+pub mod cli;
+pub mod commands;
 pub mod config;
-pub mod constants;
+pub mod discovery;
+pub mod errors;
 pub mod helpers;
 pub mod init;
+#[cfg(test)]
+mod mock_rpc;
+pub mod prices;
+pub mod reconcile;
+pub mod registry;
+pub mod retry;
+pub mod scanner;
+pub mod summary;
+pub mod tokens;
+pub mod transactions;
+pub mod transfers;
+pub mod wallets;
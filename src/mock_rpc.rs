@@ -0,0 +1,435 @@
+//! Minimal local JSON-RPC server used by the tests to serve canned responses over HTTP
+
+use crate::discovery::{ILiquidationPair, IERC4626};
+
+use alloy::{
+    primitives::{address, Address, LogData, TxHash, U256},
+    sol_types::{SolCall, SolValue},
+};
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+
+/// Response of the mock server to a single JSON-RPC request
+pub enum MockResponse {
+    /// JSON-RPC result
+    Result(Value),
+    /// JSON-RPC error with a code and a message
+    Error(i64, String),
+    /// HTTP error status with an empty body, e.g. 429 when rate limited
+    Status(u16),
+}
+
+/// Local JSON-RPC server answering each request with a handler
+pub struct MockRpc {
+    /// URL of the server
+    pub url: reqwest::Url,
+    /// Number of requests received
+    counter: Arc<AtomicUsize>,
+}
+
+impl MockRpc {
+    /// Start a server on a random local port, `handler` receives the method and params of each
+    /// request along with the number of requests received before it
+    pub async fn start<H>(handler: H) -> Self
+    where
+        H: Fn(&str, &Value, usize) -> MockResponse + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap())
+            .parse()
+            .unwrap();
+        let handler = Arc::new(handler);
+        let counter = Arc::new(AtomicUsize::new(0));
+
+        let server_counter = counter.clone();
+
+        tokio::spawn(async move {
+            let counter = server_counter;
+
+            while let Ok((stream, _)) = listener.accept().await {
+                let handler = handler.clone();
+                let counter = counter.clone();
+
+                tokio::spawn(async move {
+                    let (reader, mut writer) = stream.into_split();
+                    let mut reader = BufReader::new(reader);
+
+                    // Connections are kept alive, so several requests are read from each one.
+                    while let Some(body) = read_request(&mut reader).await {
+                        let request: Value = serde_json::from_slice(&body).unwrap();
+                        let index = counter.fetch_add(1, Ordering::SeqCst);
+                        let method = request["method"].as_str().unwrap_or_default();
+
+                        let (status, body) = match handler(method, &request["params"], index) {
+                            MockResponse::Result(result) => (
+                                200,
+                                json!({"jsonrpc": "2.0", "id": request["id"], "result": result}),
+                            ),
+                            MockResponse::Error(code, message) => (
+                                200,
+                                json!({
+                                    "jsonrpc": "2.0",
+                                    "id": request["id"],
+                                    "error": {"code": code, "message": message}
+                                }),
+                            ),
+                            MockResponse::Status(status) => (status, Value::Null),
+                        };
+
+                        let body = match body {
+                            Value::Null => String::new(),
+                            body => body.to_string(),
+                        };
+                        let response = format!(
+                            "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                            status,
+                            body.len(),
+                            body
+                        );
+
+                        if writer.write_all(response.as_bytes()).await.is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+
+        Self { url, counter }
+    }
+
+    /// Number of requests received so far
+    pub fn request_count(&self) -> usize {
+        self.counter.load(Ordering::SeqCst)
+    }
+}
+
+/// Read the body of the next HTTP request of a connection, `None` once it is closed
+async fn read_request<R: AsyncBufReadExt + Unpin>(reader: &mut R) -> Option<Vec<u8>> {
+    let mut content_length = 0;
+
+    loop {
+        let mut line = String::new();
+
+        if reader.read_line(&mut line).await.ok()? == 0 {
+            return None;
+        }
+
+        let line = line.trim_end();
+
+        if line.is_empty() {
+            break;
+        }
+
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().ok()?;
+            }
+        }
+    }
+
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).await.ok()?;
+
+    Some(body)
+}
+
+/// Block header with a given number and timestamp, as returned by `eth_getBlockByNumber`
+pub fn block(number: u64, timestamp: u64) -> Value {
+    let zero_hash = format!("0x{:064x}", 0);
+
+    json!({
+        "hash": format!("0x{:064x}", number + 1),
+        "parentHash": format!("0x{:064x}", number),
+        "sha3Uncles": zero_hash,
+        "miner": format!("0x{:040x}", 0),
+        "stateRoot": zero_hash,
+        "transactionsRoot": zero_hash,
+        "receiptsRoot": zero_hash,
+        "logsBloom": format!("0x{}", "00".repeat(256)),
+        "difficulty": "0x0",
+        "number": format!("0x{:x}", number),
+        "gasLimit": "0x1",
+        "gasUsed": "0x0",
+        "timestamp": format!("0x{:x}", timestamp),
+        "extraData": "0x",
+        "mixHash": zero_hash,
+        "nonce": "0x0000000000000000",
+        "baseFeePerGas": "0x1",
+        "size": "0x1",
+        "uncles": [],
+        "transactions": [],
+        "totalDifficulty": "0x0"
+    })
+}
+
+/// Parse a hexadecimal quantity of a JSON-RPC request
+pub fn quantity(value: &Value) -> u64 {
+    u64::from_str_radix(value.as_str().unwrap().trim_start_matches("0x"), 16).unwrap()
+}
+
+/// Timestamp of the genesis block of `MockChain`
+pub const GENESIS_TIMESTAMP: u64 = 1_700_000_000;
+
+/// Seconds between two blocks of `MockChain`
+pub const BLOCK_TIME: u64 = 2;
+
+/// Chain serving its blocks, logs, receipts and contract calls over a `MockRpc`,
+/// every block up to `latest_block` being finalized
+#[derive(Clone, Default)]
+pub struct MockChain {
+    pub latest_block: u64,
+    /// Logs of the transactions, in block and log index order
+    logs: Vec<Value>,
+    receipts: Vec<Value>,
+    /// Results of the calls to a contract with a given selector
+    calls: Vec<(Address, [u8; 4], Value)>,
+}
+
+impl MockChain {
+    pub fn new(latest_block: u64) -> Self {
+        Self {
+            latest_block,
+            ..Default::default()
+        }
+    }
+
+    /// Timestamp of a block
+    pub fn timestamp(block_number: u64) -> u64 {
+        GENESIS_TIMESTAMP + BLOCK_TIME * block_number
+    }
+
+    /// Add a successful transaction sent by `from` with the given logs at the end of a block,
+    /// paying a fee of 21000 gas at 1 gwei
+    pub fn transaction(
+        &mut self,
+        block_number: u64,
+        from: Address,
+        logs: Vec<(Address, LogData)>,
+    ) -> TxHash {
+        let transaction_index = self
+            .receipts
+            .iter()
+            .filter(|receipt| quantity(&receipt["blockNumber"]) == block_number)
+            .count() as u64;
+        let first_log_index = self
+            .logs
+            .iter()
+            .filter(|log| quantity(&log["blockNumber"]) == block_number)
+            .count() as u64;
+        let tx_hash = TxHash::from(U256::from(block_number * 1000 + transaction_index + 1));
+
+        let logs: Vec<Value> = logs
+            .into_iter()
+            .enumerate()
+            .map(|(index, (address, data))| {
+                json!({
+                    "address": address,
+                    "topics": data.topics(),
+                    "data": data.data,
+                    "blockNumber": format!("0x{:x}", block_number),
+                    "blockHash": format!("0x{:064x}", block_number + 1),
+                    "transactionHash": tx_hash,
+                    "transactionIndex": format!("0x{:x}", transaction_index),
+                    "logIndex": format!("0x{:x}", first_log_index + index as u64),
+                    "removed": false
+                })
+            })
+            .collect();
+
+        self.receipts.push(json!({
+            "type": "0x2",
+            "status": "0x1",
+            "cumulativeGasUsed": "0x5208",
+            "logs": logs,
+            "logsBloom": format!("0x{}", "00".repeat(256)),
+            "transactionHash": tx_hash,
+            "transactionIndex": format!("0x{:x}", transaction_index),
+            "blockHash": format!("0x{:064x}", block_number + 1),
+            "blockNumber": format!("0x{:x}", block_number),
+            "gasUsed": "0x5208",
+            "effectiveGasPrice": "0x3b9aca00",
+            "from": from,
+            "to": Address::ZERO,
+            "contractAddress": null
+        }));
+
+        self.logs.extend(logs);
+        self.logs
+            .sort_by_key(|log| (quantity(&log["blockNumber"]), quantity(&log["logIndex"])));
+
+        tx_hash
+    }
+
+    /// Answer the calls of `selector` to a contract with an ABI-encoded result
+    pub fn call(&mut self, to: Address, selector: [u8; 4], result: Vec<u8>) {
+        self.calls
+            .push((to, selector, json!(alloy::hex::encode_prefixed(result))));
+    }
+
+    /// Answer the calls resolving `PAIR`, its token out being the shares of `VAULT`
+    pub fn with_pair(mut self) -> Self {
+        self.call(
+            PAIR,
+            ILiquidationPair::sourceCall::SELECTOR,
+            VAULT.abi_encode(),
+        );
+        self.call(
+            PAIR,
+            ILiquidationPair::tokenOutCall::SELECTOR,
+            VAULT.abi_encode(),
+        );
+        self.call(VAULT, IERC4626::assetCall::SELECTOR, ASSET.abi_encode());
+        self
+    }
+
+    /// Start a server answering the requests with `respond`
+    pub async fn start(self) -> MockRpc {
+        MockRpc::start(move |method, params, _| self.respond(method, params)).await
+    }
+
+    /// Response of the chain to a request, calls without a result reverting
+    pub fn respond(&self, method: &str, params: &Value) -> MockResponse {
+        match method {
+            "eth_chainId" => MockResponse::Result(json!("0xa")),
+            "eth_blockNumber" => MockResponse::Result(json!(format!("0x{:x}", self.latest_block))),
+            "eth_getBlockByNumber" => {
+                let number = match params[0].as_str() {
+                    Some("latest" | "finalized" | "safe") => self.latest_block,
+                    _ => quantity(&params[0]),
+                };
+
+                MockResponse::Result(if number <= self.latest_block {
+                    block(number, Self::timestamp(number))
+                } else {
+                    Value::Null
+                })
+            }
+            "eth_getLogs" => MockResponse::Result(Value::Array(
+                self.logs
+                    .iter()
+                    .filter(|log| matches_filter(log, &params[0]))
+                    .cloned()
+                    .collect(),
+            )),
+            "eth_getTransactionReceipt" => MockResponse::Result(
+                self.receipts
+                    .iter()
+                    .find(|receipt| receipt["transactionHash"] == params[0])
+                    .cloned()
+                    .unwrap_or_default(),
+            ),
+            "eth_call" => {
+                let to: Address = params[0]["to"].as_str().unwrap().parse().unwrap();
+                let input = params[0]["input"]
+                    .as_str()
+                    .or(params[0]["data"].as_str())
+                    .unwrap();
+                let selector = alloy::hex::decode(input).unwrap();
+
+                match self.calls.iter().find(|(address, call_selector, _)| {
+                    *address == to && selector.starts_with(call_selector)
+                }) {
+                    Some((_, _, result)) => MockResponse::Result(result.clone()),
+                    None => MockResponse::Error(3, "execution reverted".to_string()),
+                }
+            }
+            _ => MockResponse::Error(-32601, format!("unsupported method {}", method)),
+        }
+    }
+}
+
+/// Whether a log matches the block range, addresses and topics of a `eth_getLogs` filter
+fn matches_filter(log: &Value, filter: &Value) -> bool {
+    let block_number = quantity(&log["blockNumber"]);
+    let in_set = |set: &Value, value: &Value| match set {
+        Value::Null => true,
+        Value::Array(values) => values.is_empty() || values.iter().any(|item| eq_hex(item, value)),
+        item => eq_hex(item, value),
+    };
+
+    let topics_match = filter["topics"].as_array().is_none_or(|topics| {
+        topics.iter().enumerate().all(|(index, set)| {
+            set.is_null()
+                || log["topics"]
+                    .get(index)
+                    .is_some_and(|topic| in_set(set, topic))
+        })
+    });
+
+    filter["fromBlock"]
+        .as_str()
+        .is_none_or(|_| quantity(&filter["fromBlock"]) <= block_number)
+        && filter["toBlock"]
+            .as_str()
+            .is_none_or(|_| quantity(&filter["toBlock"]) >= block_number)
+        && in_set(&filter["address"], &log["address"])
+        && topics_match
+}
+
+/// Whether two hexadecimal strings are equal, ignoring their case
+fn eq_hex(a: &Value, b: &Value) -> bool {
+    a.as_str()
+        .unwrap_or_default()
+        .eq_ignore_ascii_case(b.as_str().unwrap_or_default())
+}
+
+/// Liquidation router of the test registry
+pub const ROUTER: Address = address!("00000000000000000000000000000000000000e0");
+/// Prize token of the test registry, POOL
+pub const PRIZE_TOKEN: Address = address!("00000000000000000000000000000000000000c0");
+/// Liquidation pair of the test registry, liquidating the yield of `VAULT` as vault shares
+pub const PAIR: Address = address!("00000000000000000000000000000000000000a0");
+/// Prize vault of `PAIR`, whose shares are przDAI
+pub const VAULT: Address = address!("00000000000000000000000000000000000000b0");
+/// Underlying asset of `VAULT`, DAI
+pub const ASSET: Address = address!("00000000000000000000000000000000000000d0");
+
+/// Write a registry of chain 10 with the router, prize token, pair and tokens above,
+/// returning its path
+pub fn write_registry(dir: &Path) -> PathBuf {
+    let path = dir.join("registry.toml");
+
+    std::fs::create_dir_all(dir).unwrap();
+    std::fs::write(
+        &path,
+        format!(
+            r#"
+version = 1
+
+[[chains]]
+name = "Test"
+chain_id = 10
+liquidation_router = "{ROUTER}"
+prize_token = "{PRIZE_TOKEN}"
+fee_token_symbol = "ETH"
+
+[[chains.liquidation_pairs]]
+address = "{PAIR}"
+underlying_asset = "{ASSET}"
+
+[[chains.tokens]]
+address = "{PRIZE_TOKEN}"
+symbol = "POOL"
+decimals = 18
+
+[[chains.tokens]]
+address = "{VAULT}"
+symbol = "przDAI"
+decimals = 18
+
+[[chains.tokens]]
+address = "{ASSET}"
+symbol = "DAI"
+decimals = 18
+"#
+        ),
+    )
+    .unwrap();
+
+    path
+}
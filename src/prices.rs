@@ -0,0 +1,686 @@
+use crate::{
+    errors::BotError,
+    registry::PriceFeedInfo,
+    summary::{format_amount, parse_amount, AMOUNT_DECIMALS},
+    tokens::TokenResolver,
+};
+
+use alloy::{
+    eips::BlockId,
+    network::AnyNetwork,
+    primitives::{utils::format_units, Address, I256},
+    providers::Provider,
+    sol,
+    transports::Transport,
+};
+use chrono::{DateTime, NaiveDate};
+use eyre::{eyre, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+sol! {
+    #[sol(rpc)]
+    interface AggregatorV3Interface {
+        function decimals() external view returns (uint8);
+        function latestRoundData() external view returns (
+            uint80 roundId,
+            int256 answer,
+            uint256 startedAt,
+            uint256 updatedAt,
+            uint80 answeredInRound
+        );
+    }
+
+    #[sol(rpc)]
+    interface IUniswapV3Pool {
+        function token0() external view returns (address);
+        function token1() external view returns (address);
+        function observe(uint32[] secondsAgos) external view returns (
+            int56[] tickCumulatives,
+            uint160[] secondsPerLiquidityCumulativeX128s
+        );
+    }
+}
+
+/// Default currency in which rows are valued
+pub const DEFAULT_NET_WORTH_CURRENCY: &str = "USD";
+
+/// Default period in seconds over which TWAP prices are averaged
+pub const DEFAULT_TWAP_SECONDS: u32 = 1800;
+
+/// Default age in seconds above which the answers of Chainlink feeds are considered stale,
+/// the longest heartbeat of the common feeds
+pub const DEFAULT_CHAINLINK_HEARTBEAT: u64 = 86400;
+
+/// Largest tick of a Uniswap V3 pool
+const MAX_TICK: i32 = 887272;
+
+/// Source of the price of a currency
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PriceSource {
+    /// Daily prices of a local CSV file
+    Csv,
+    /// Answer of a Chainlink price feed at the block of the row
+    Chainlink,
+    /// Time-weighted average price of a Uniswap V3 pool ending at the block of the row
+    Twap,
+}
+
+impl FromStr for PriceSource {
+    type Err = eyre::Report;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value {
+            "csv" => Ok(Self::Csv),
+            "chainlink" => Ok(Self::Chainlink),
+            "twap" => Ok(Self::Twap),
+            _ => Err(eyre!(
+                "Failed to parse \"PRICE_SOURCES\", expected \"csv\", \"chainlink\" or \"twap\""
+            )),
+        }
+    }
+}
+
+impl fmt::Display for PriceSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Csv => write!(f, "csv"),
+            Self::Chainlink => write!(f, "chainlink"),
+            Self::Twap => write!(f, "twap"),
+        }
+    }
+}
+
+/// Sources used to value the rows
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PriceConfig {
+    /// Sources of the prices, in order of precedence. Rows are not valued if empty.
+    pub sources: Vec<PriceSource>,
+    /// CSV file of daily prices, required by the `csv` source
+    pub file: Option<PathBuf>,
+    /// Currency in which prices are given, e.g. USD
+    pub currency: String,
+    /// Period in seconds over which TWAP prices are averaged
+    pub twap_seconds: u32,
+    /// Age in seconds above which the answer of a Chainlink feed is not used
+    pub chainlink_heartbeat: u64,
+}
+
+impl PriceConfig {
+    /// Whether a price source is configured
+    pub fn is_enabled(&self) -> bool {
+        !self.sources.is_empty()
+    }
+}
+
+/// Price of one unit of a currency, with the decimals of `parse_amount`
+pub struct Price {
+    pub value: I256,
+    pub source: PriceSource,
+}
+
+impl Price {
+    /// Value of a decimal amount of the currency
+    pub fn value_of(&self, amount: &str) -> Result<String, BotError> {
+        let amount = parse_amount(amount).map_err(|error| BotError::Decode(error.to_string()))?;
+
+        Ok(format_amount(amount * self.value / unit()))
+    }
+}
+
+/// Daily price of a currency in the prices CSV file
+#[derive(Deserialize)]
+struct PriceRow {
+    #[serde(rename = "Date")]
+    date: String,
+    #[serde(rename = "Currency")]
+    currency: String,
+    #[serde(rename = "Price")]
+    price: String,
+}
+
+/// Tokens of a Uniswap V3 pool with their symbols and decimals
+#[derive(Clone)]
+struct PoolTokens {
+    token0: (String, u8),
+    token1: (String, u8),
+}
+
+/// Resolves the prices of currencies at a given block from the configured sources
+pub struct PriceResolver<T, P> {
+    /// Provider
+    provider: Arc<P>,
+    /// Resolver of the symbols and decimals of the tokens of the pools
+    tokens: Arc<TokenResolver<T, P>>,
+    /// Sources of the prices
+    config: PriceConfig,
+    /// Chainlink feeds and Uniswap V3 pools of the registry
+    feeds: Vec<PriceFeedInfo>,
+    /// Prices of the CSV file, keyed by currency and UTC day
+    daily_prices: HashMap<(String, NaiveDate), I256>,
+    /// Decimals of the Chainlink feeds, read once
+    feed_decimals: Mutex<HashMap<Address, u8>>,
+    /// Tokens of the Uniswap V3 pools, read once
+    pools: Mutex<HashMap<Address, PoolTokens>>,
+}
+
+impl<T, P> PriceResolver<T, P>
+where
+    T: Transport + Clone,
+    P: Provider<T, AnyNetwork>,
+{
+    pub fn new(
+        provider: Arc<P>,
+        tokens: Arc<TokenResolver<T, P>>,
+        config: PriceConfig,
+        feeds: Vec<PriceFeedInfo>,
+    ) -> Result<Self> {
+        let daily_prices = match (&config.file, config.sources.contains(&PriceSource::Csv)) {
+            (Some(path), true) => read_daily_prices(path)?,
+            _ => HashMap::new(),
+        };
+
+        Ok(Self {
+            provider,
+            tokens,
+            config,
+            feeds,
+            daily_prices,
+            feed_decimals: Mutex::new(HashMap::new()),
+            pools: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Whether a price source is configured
+    pub fn is_enabled(&self) -> bool {
+        self.config.is_enabled()
+    }
+
+    /// Currency in which prices are given
+    pub fn currency(&self) -> &str {
+        &self.config.currency
+    }
+
+    /// Price of a currency at a given block from the first source that has one,
+    /// `None` if no source prices the currency. Failures of a source, e.g. a reverted call to a feed
+    /// or a pool without enough observations, are logged and the next source is tried.
+    pub async fn get_price(
+        &self,
+        currency: &str,
+        block_number: u64,
+        timestamp: u64,
+    ) -> Option<Price> {
+        for source in &self.config.sources {
+            let value = match source {
+                PriceSource::Twap => self.get_twap_price(currency, block_number, timestamp).await,
+                _ => {
+                    self.get_direct_price(*source, currency, block_number, timestamp)
+                        .await
+                }
+            };
+
+            match value {
+                Ok(Some(value)) => {
+                    return Some(Price {
+                        value,
+                        source: *source,
+                    })
+                }
+                Ok(None) => {}
+                Err(error) => log::warn!("{}, trying the next price source", error),
+            }
+        }
+
+        None
+    }
+
+    /// Price of a currency in the net worth currency from the first source quoting it directly,
+    /// used to convert TWAP prices
+    async fn get_quote_price(
+        &self,
+        currency: &str,
+        block_number: u64,
+        timestamp: u64,
+    ) -> Option<I256> {
+        if currency == self.config.currency {
+            return Some(unit());
+        }
+
+        for source in &self.config.sources {
+            match self
+                .get_direct_price(*source, currency, block_number, timestamp)
+                .await
+            {
+                Ok(Some(price)) => return Some(price),
+                Ok(None) => {}
+                Err(error) => log::warn!("{}, trying the next price source", error),
+            }
+        }
+
+        None
+    }
+
+    /// Price of a currency from a source that quotes it directly in the net worth currency
+    async fn get_direct_price(
+        &self,
+        source: PriceSource,
+        currency: &str,
+        block_number: u64,
+        timestamp: u64,
+    ) -> Result<Option<I256>, BotError> {
+        match source {
+            PriceSource::Csv => {
+                let date = DateTime::from_timestamp(timestamp as i64, 0)
+                    .ok_or_else(|| BotError::Decode(format!("timestamp {}", timestamp)))?
+                    .date_naive();
+
+                Ok(self
+                    .daily_prices
+                    .get(&(currency.to_string(), date))
+                    .copied())
+            }
+            PriceSource::Chainlink => match self.get_feed(currency, PriceSource::Chainlink) {
+                Some(feed) => {
+                    self.get_chainlink_price(currency, feed, block_number, timestamp)
+                        .await
+                }
+                None => Ok(None),
+            },
+            PriceSource::Twap => Ok(None),
+        }
+    }
+
+    /// Address of the feed of a currency for a given source in the registry
+    fn get_feed(&self, currency: &str, source: PriceSource) -> Option<Address> {
+        self.feeds
+            .iter()
+            .find(|feed| feed.currency == currency && feed.source == source)
+            .map(|feed| feed.address)
+    }
+
+    /// Read the answer of a Chainlink feed at a given block, which must have been updated within
+    /// the heartbeat before the timestamp of the block
+    async fn get_chainlink_price(
+        &self,
+        currency: &str,
+        feed: Address,
+        block_number: u64,
+        timestamp: u64,
+    ) -> Result<Option<I256>, BotError> {
+        let aggregator = AggregatorV3Interface::new(feed, self.provider.as_ref());
+        let price_error = |error: alloy::contract::Error| BotError::Price {
+            currency: currency.to_string(),
+            reason: format!("Chainlink feed {}: {}", feed, error),
+        };
+
+        let cached_decimals = self.feed_decimals.lock().unwrap().get(&feed).copied();
+        let decimals = match cached_decimals {
+            Some(decimals) => decimals,
+            None => {
+                let decimals = aggregator.decimals().call().await.map_err(price_error)?._0;
+
+                self.feed_decimals.lock().unwrap().insert(feed, decimals);
+
+                decimals
+            }
+        };
+
+        let round = aggregator
+            .latestRoundData()
+            .block(BlockId::number(block_number))
+            .call()
+            .await
+            .map_err(price_error)?;
+
+        if round.answer <= I256::ZERO {
+            return Ok(None);
+        }
+
+        let updated_at = u64::try_from(round.updatedAt).unwrap_or(u64::MAX);
+
+        if is_stale(updated_at, timestamp, self.config.chainlink_heartbeat) {
+            return Err(BotError::Price {
+                currency: currency.to_string(),
+                reason: format!(
+                    "Chainlink feed {} was last updated at {}, more than {} seconds before {}",
+                    feed, updated_at, self.config.chainlink_heartbeat, timestamp
+                ),
+            });
+        }
+
+        let answer = format_units(round.answer, decimals).map_err(|error| BotError::Price {
+            currency: currency.to_string(),
+            reason: format!("Chainlink feed {}: {}", feed, error),
+        })?;
+
+        to_price(currency, &answer).map(Some)
+    }
+
+    /// Time-weighted average price of a currency in the other token of its Uniswap V3 pool,
+    /// converted to the net worth currency with the direct price of the other token
+    async fn get_twap_price(
+        &self,
+        currency: &str,
+        block_number: u64,
+        timestamp: u64,
+    ) -> Result<Option<I256>, BotError> {
+        let Some(pool) = self.get_feed(currency, PriceSource::Twap) else {
+            return Ok(None);
+        };
+
+        let price_error = |reason: String| BotError::Price {
+            currency: currency.to_string(),
+            reason: format!("Uniswap V3 pool {}: {}", pool, reason),
+        };
+
+        let tokens = self.get_pool_tokens(pool).await.map_err(price_error)?;
+        let window = self.config.twap_seconds;
+
+        let tick_cumulatives = IUniswapV3Pool::new(pool, self.provider.as_ref())
+            .observe(vec![window, 0])
+            .block(BlockId::number(block_number))
+            .call()
+            .await
+            .map_err(|error| price_error(error.to_string()))?
+            .tickCumulatives;
+
+        let [start, end] = tick_cumulatives[..] else {
+            return Err(price_error("unexpected observations".to_string()));
+        };
+
+        let tick = mean_tick(
+            i64::try_from(start).unwrap_or_default(),
+            i64::try_from(end).unwrap_or_default(),
+            window,
+        )
+        .ok_or_else(|| price_error("mean tick out of range".to_string()))?;
+
+        let (base_is_token0, quote) = if tokens.token0.0 == currency {
+            (true, &tokens.token1.0)
+        } else if tokens.token1.0 == currency {
+            (false, &tokens.token0.0)
+        } else {
+            return Err(price_error(format!(
+                "{} is not a token of the pool",
+                currency
+            )));
+        };
+
+        let price = tick_to_price(tick, tokens.token0.1, tokens.token1.1, base_is_token0)
+            .ok_or_else(|| price_error(format!("price out of range at tick {}", tick)))?;
+
+        let Some(quote_price) = self.get_quote_price(quote, block_number, timestamp).await else {
+            return Ok(None);
+        };
+
+        let price = to_price(currency, &format!("{:.18}", price))?;
+
+        Ok(Some(price * quote_price / unit()))
+    }
+
+    /// Read the tokens of a Uniswap V3 pool and their symbols and decimals
+    async fn get_pool_tokens(&self, pool: Address) -> Result<PoolTokens, String> {
+        if let Some(tokens) = self.pools.lock().unwrap().get(&pool) {
+            return Ok(tokens.clone());
+        }
+
+        let contract = IUniswapV3Pool::new(pool, self.provider.as_ref());
+        let token0 = contract
+            .token0()
+            .call()
+            .await
+            .map_err(|error| error.to_string())?
+            ._0;
+        let token1 = contract
+            .token1()
+            .call()
+            .await
+            .map_err(|error| error.to_string())?
+            ._0;
+
+        let token0 = self
+            .tokens
+            .get_token(token0)
+            .await
+            .map_err(|error| error.to_string())?;
+        let token1 = self
+            .tokens
+            .get_token(token1)
+            .await
+            .map_err(|error| error.to_string())?;
+
+        let tokens = PoolTokens {
+            token0: (token0.symbol, token0.decimals),
+            token1: (token1.symbol, token1.decimals),
+        };
+
+        self.pools.lock().unwrap().insert(pool, tokens.clone());
+
+        Ok(tokens)
+    }
+}
+
+/// Mean tick of a Uniswap V3 pool over a window from the tick cumulatives at its start and end,
+/// rounded towards negative infinity like the Uniswap oracle library. `None` if it is not a valid tick.
+fn mean_tick(start: i64, end: i64, window: u32) -> Option<i32> {
+    let delta = end.checked_sub(start)?;
+    let mut tick = delta.checked_div(i64::from(window))?;
+
+    if delta < 0 && delta % i64::from(window) != 0 {
+        tick -= 1;
+    }
+
+    i32::try_from(tick)
+        .ok()
+        .filter(|tick| tick.abs() <= MAX_TICK)
+}
+
+/// Price at a given tick of token0 in token1, or of token1 in token0 if `base_is_token0` is false,
+/// adjusted for their decimals. `None` if it overflows or rounds to zero.
+fn tick_to_price(tick: i32, decimals0: u8, decimals1: u8, base_is_token0: bool) -> Option<f64> {
+    let price = 1.0001_f64.powi(tick) * 10_f64.powi(i32::from(decimals0) - i32::from(decimals1));
+    let price = if base_is_token0 { price } else { 1.0 / price };
+
+    (price.is_finite() && price > 0.0).then_some(price)
+}
+
+/// Whether an answer updated at `updated_at` is older than `heartbeat` seconds at `timestamp`
+fn is_stale(updated_at: u64, timestamp: u64, heartbeat: u64) -> bool {
+    timestamp.saturating_sub(updated_at) > heartbeat
+}
+
+/// One unit of a currency, with the decimals of `parse_amount`
+fn unit() -> I256 {
+    I256::exp10(usize::from(AMOUNT_DECIMALS))
+}
+
+/// Parse a decimal price
+fn to_price(currency: &str, price: &str) -> Result<I256, BotError> {
+    parse_amount(price).map_err(|error| BotError::Price {
+        currency: currency.to_string(),
+        reason: error.to_string(),
+    })
+}
+
+/// Read the daily prices of a CSV file with Date (YYYY-MM-DD), Currency and Price columns
+fn read_daily_prices(path: &Path) -> Result<HashMap<(String, NaiveDate), I256>> {
+    let mut reader = csv::Reader::from_path(path)
+        .map_err(|error| eyre!("Failed to open {}: {}", path.display(), error))?;
+
+    let mut prices = HashMap::new();
+
+    for row in reader.deserialize() {
+        let row: PriceRow =
+            row.map_err(|error| eyre!("Failed to read {}: {}", path.display(), error))?;
+
+        let date = row.date.parse::<NaiveDate>().map_err(|_| {
+            eyre!(
+                "Failed to parse date \"{}\" of {}, expected YYYY-MM-DD",
+                row.date,
+                path.display()
+            )
+        })?;
+
+        prices.insert((row.currency, date), parse_amount(&row.price)?);
+    }
+
+    Ok(prices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        mock_rpc::{MockResponse, MockRpc},
+        registry::Registry,
+    };
+
+    use alloy::{
+        hex,
+        primitives::{address, U256},
+        providers::{ProviderBuilder, RootProvider},
+        sol_types::{SolCall, SolValue},
+        transports::http::{Client as HttpClient, Http},
+    };
+    use serde_json::json;
+
+    const FEED: Address = address!("00000000000000000000000000000000000000f1");
+
+    /// Assert that a price is within a relative tolerance of the expected one
+    fn assert_close(price: Option<f64>, expected: f64, tolerance: f64) {
+        let price = price.unwrap();
+
+        assert!(
+            (price - expected).abs() <= expected * tolerance,
+            "{} != {}",
+            price,
+            expected
+        );
+    }
+
+    #[test]
+    fn rounds_mean_tick_towards_negative_infinity() {
+        assert_eq!(mean_tick(0, 1800 * 10, 1800), Some(10));
+        assert_eq!(mean_tick(0, 1800 * 10 + 1, 1800), Some(10));
+        assert_eq!(mean_tick(0, -1800 * 10, 1800), Some(-10));
+        assert_eq!(mean_tick(0, -1800 * 10 - 1, 1800), Some(-11));
+        assert_eq!(mean_tick(0, 1, 0), None);
+        assert_eq!(mean_tick(0, i64::from(MAX_TICK + 1), 1), None);
+    }
+
+    #[test]
+    fn converts_ticks_to_prices() {
+        assert_close(tick_to_price(0, 18, 18, true), 1.0, 1e-12);
+        assert_close(tick_to_price(6932, 18, 18, true), 2.0, 1e-4);
+        assert_close(tick_to_price(6932, 18, 18, false), 0.5, 1e-4);
+
+        // WETH (18 decimals) in USDC (6 decimals) at about 2000 USDC per WETH, ticks being
+        // spaced by 0.01%.
+        assert_close(tick_to_price(-200311, 18, 6, true), 2000.0, 1e-4);
+        assert_close(tick_to_price(-200311, 18, 6, false), 1.0 / 2000.0, 1e-4);
+    }
+
+    #[test]
+    fn rejects_prices_out_of_range() {
+        assert_eq!(tick_to_price(i32::MAX, 18, 18, true), None);
+        assert_eq!(tick_to_price(i32::MAX, 18, 18, false), None);
+    }
+
+    #[test]
+    fn rejects_answers_older_than_the_heartbeat() {
+        assert!(!is_stale(1000, 1000, 3600));
+        assert!(!is_stale(1000, 4600, 3600));
+        assert!(is_stale(1000, 4601, 3600));
+        assert!(!is_stale(2000, 1000, 3600));
+    }
+
+    #[tokio::test]
+    async fn falls_through_stale_chainlink_answers() {
+        // The feed answers 2000 with 8 decimals, last updated at 1000.
+        let rpc = MockRpc::start(|method, params, _| {
+            let input = hex::decode(
+                params[0]["input"]
+                    .as_str()
+                    .or(params[0]["data"].as_str())
+                    .unwrap_or_default(),
+            )
+            .unwrap_or_default();
+
+            match method {
+                "eth_call" if input.starts_with(&AggregatorV3Interface::decimalsCall::SELECTOR) => {
+                    MockResponse::Result(json!(hex::encode_prefixed(U256::from(8).abi_encode())))
+                }
+                "eth_call" => MockResponse::Result(json!(hex::encode_prefixed(
+                    (
+                        U256::from(1),
+                        I256::try_from(2000_0000_0000_i64).unwrap(),
+                        U256::from(1000),
+                        U256::from(1000),
+                        U256::from(1),
+                    )
+                        .abi_encode()
+                ))),
+                _ => MockResponse::Error(-32601, format!("unsupported method {}", method)),
+            }
+        })
+        .await;
+
+        let provider: Arc<RootProvider<Http<HttpClient>, AnyNetwork>> = Arc::new(
+            ProviderBuilder::new()
+                .network::<AnyNetwork>()
+                .on_http(rpc.url.clone()),
+        );
+        let registry = Registry::load(None).unwrap().chains[0].clone();
+        let dir = std::env::temp_dir().join(format!("prices-{}", rand::random::<u64>()));
+        let file = dir.join("prices.csv");
+
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(&file, "Date,Currency,Price\n1970-01-01,WETH,1900\n").unwrap();
+
+        let tokens = Arc::new(TokenResolver::new(provider.clone(), registry, &dir).unwrap());
+        let prices = PriceResolver::new(
+            provider,
+            tokens,
+            PriceConfig {
+                sources: vec![PriceSource::Chainlink, PriceSource::Csv],
+                file: Some(file),
+                currency: DEFAULT_NET_WORTH_CURRENCY.to_string(),
+                twap_seconds: DEFAULT_TWAP_SECONDS,
+                chainlink_heartbeat: 3600,
+            },
+            vec![PriceFeedInfo {
+                currency: "WETH".to_string(),
+                source: PriceSource::Chainlink,
+                address: FEED,
+            }],
+        )
+        .unwrap();
+
+        let fresh = prices.get_price("WETH", 1, 4600).await.unwrap();
+        assert_eq!(fresh.source, PriceSource::Chainlink);
+        assert_eq!(fresh.value, parse_amount("2000").unwrap());
+
+        let stale = prices.get_price("WETH", 1, 4601).await.unwrap();
+        assert_eq!(stale.source, PriceSource::Csv);
+        assert_eq!(stale.value, parse_amount("1900").unwrap());
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn values_amounts() {
+        let price = Price {
+            value: parse_amount("2000.5").unwrap(),
+            source: PriceSource::Chainlink,
+        };
+
+        assert_eq!(price.value_of("1.5").unwrap(), "3000.75");
+        assert_eq!(price.value_of("0").unwrap(), "0");
+        assert!(price.value_of("not an amount").is_err());
+    }
+}
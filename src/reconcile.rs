@@ -0,0 +1,336 @@
+use crate::{
+    registry::TokenInfo,
+    summary::{parse_amount, Summary},
+};
+
+use alloy::{
+    eips::BlockId,
+    network::AnyNetwork,
+    primitives::{utils::format_units, Address, I256, U256},
+    providers::Provider,
+    sol,
+    transports::Transport,
+};
+use eyre::{eyre, Result};
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+sol! {
+    #[sol(rpc)]
+    interface IERC20 {
+        function balanceOf(address account) external view returns (uint256);
+    }
+}
+
+/// Decimals of the native currency
+const NATIVE_DECIMALS: u8 = 18;
+
+/// Change of the balance of an asset of a wallet over a period, compared to the net amount
+/// recorded in a Koinly CSV file. Amounts use the decimals of `parse_amount`.
+pub struct AssetReconciliation {
+    pub symbol: String,
+    /// Address of the token, `None` for the native currency
+    pub token: Option<Address>,
+    /// Balance at the end of the block before the period
+    pub start_balance: I256,
+    /// Balance at the end of the last block of the period
+    pub end_balance: I256,
+    /// Net amount received according to the CSV file, fees included
+    pub recorded: I256,
+}
+
+impl AssetReconciliation {
+    /// Part of the balance change that is not explained by the CSV file,
+    /// e.g. transfers or transactions that were not exported
+    pub fn unexplained(&self) -> I256 {
+        self.end_balance - self.start_balance - self.recorded
+    }
+}
+
+/// Reconciliation of the balances of a wallet with a Koinly CSV file
+pub struct Reconciliation {
+    /// Assets of the CSV file whose balances were read
+    pub assets: Vec<AssetReconciliation>,
+    /// Currencies of the CSV file that could not be matched to a single known token
+    pub unmatched: Vec<String>,
+}
+
+/// Reads the balances of wallets at past blocks, which requires an archive node
+pub struct BalanceReader<T, P> {
+    /// Provider
+    provider: Arc<P>,
+    /// Symbol of the native currency, e.g. ETH
+    native_symbol: String,
+    /// Tokens whose symbols are matched against the currencies of the CSV file
+    tokens: Vec<TokenInfo>,
+    /// Tokens reconciled even without rows in the CSV file, e.g. the prize token
+    tracked_tokens: Vec<TokenInfo>,
+    phantom: PhantomData<T>,
+}
+
+impl<T, P> BalanceReader<T, P>
+where
+    T: Transport + Clone,
+    P: Provider<T, AnyNetwork>,
+{
+    pub fn new(
+        provider: Arc<P>,
+        native_symbol: String,
+        tokens: Vec<TokenInfo>,
+        tracked_tokens: Vec<TokenInfo>,
+    ) -> Self {
+        Self {
+            provider,
+            native_symbol,
+            tokens,
+            tracked_tokens,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Compare the balance changes of a wallet between `from_block` and `to_block`, both included,
+    /// with the net amounts per currency of a summarized Koinly CSV file.
+    /// Tracked tokens without rows are compared with a zero net amount, unless the wallet never held them.
+    pub async fn reconcile(
+        &self,
+        wallet: Address,
+        from_block: u64,
+        to_block: u64,
+        summary: &Summary,
+    ) -> Result<Reconciliation> {
+        let mut reconciliation = Reconciliation {
+            assets: Vec::new(),
+            unmatched: Vec::new(),
+        };
+
+        for (symbol, totals) in &summary.currencies {
+            let token = if *symbol == self.native_symbol {
+                None
+            } else {
+                match self.find_token(symbol) {
+                    Some(token) => Some(token),
+                    None => {
+                        reconciliation.unmatched.push(symbol.clone());
+                        continue;
+                    }
+                }
+            };
+
+            reconciliation.assets.push(
+                self.reconcile_asset(wallet, from_block, to_block, symbol, token, totals.net())
+                    .await?,
+            );
+        }
+
+        for token in &self.tracked_tokens {
+            if reconciliation
+                .assets
+                .iter()
+                .any(|asset| asset.token == Some(token.address))
+            {
+                continue;
+            }
+
+            let asset = self
+                .reconcile_asset(
+                    wallet,
+                    from_block,
+                    to_block,
+                    &token.symbol,
+                    Some(token),
+                    I256::ZERO,
+                )
+                .await?;
+
+            if !asset.start_balance.is_zero() || !asset.end_balance.is_zero() {
+                reconciliation.assets.push(asset);
+            }
+        }
+
+        Ok(reconciliation)
+    }
+
+    /// Read the balances of an asset before and at the end of the period
+    async fn reconcile_asset(
+        &self,
+        wallet: Address,
+        from_block: u64,
+        to_block: u64,
+        symbol: &str,
+        token: Option<&TokenInfo>,
+        recorded: I256,
+    ) -> Result<AssetReconciliation> {
+        // The balance before the period is the one at the end of the previous block.
+        let start_balance = match from_block.checked_sub(1) {
+            Some(block) => self.get_balance(wallet, token, block).await?,
+            None => I256::ZERO,
+        };
+
+        Ok(AssetReconciliation {
+            symbol: symbol.to_string(),
+            token: token.map(|token| token.address),
+            start_balance,
+            end_balance: self.get_balance(wallet, token, to_block).await?,
+            recorded,
+        })
+    }
+
+    /// Known token with the given symbol, `None` if there is none or several of them
+    fn find_token(&self, symbol: &str) -> Option<&TokenInfo> {
+        let mut tokens = self.tokens.iter().filter(|token| token.symbol == symbol);
+
+        match (tokens.next(), tokens.next()) {
+            (Some(token), None) => Some(token),
+            _ => None,
+        }
+    }
+
+    /// Read the balance of a token, or of the native currency, of a wallet at a given block
+    async fn get_balance(
+        &self,
+        wallet: Address,
+        token: Option<&TokenInfo>,
+        block: u64,
+    ) -> Result<I256> {
+        let (balance, decimals) = match token {
+            Some(token) => (
+                IERC20::new(token.address, self.provider.as_ref())
+                    .balanceOf(wallet)
+                    .block(BlockId::number(block))
+                    .call()
+                    .await
+                    .map_err(|error| {
+                        eyre!(
+                            "Failed to get the {} balance of {} at block {}: {}",
+                            token.symbol,
+                            wallet,
+                            block,
+                            error
+                        )
+                    })?
+                    ._0,
+                token.decimals,
+            ),
+            None => (
+                self.provider
+                    .get_balance(wallet)
+                    .number(block)
+                    .await
+                    .map_err(|error| {
+                        eyre!(
+                            "Failed to get the {} balance of {} at block {}: {}",
+                            self.native_symbol,
+                            wallet,
+                            block,
+                            error
+                        )
+                    })?,
+                NATIVE_DECIMALS,
+            ),
+        };
+
+        to_amount(balance, decimals)
+    }
+}
+
+/// Convert a raw balance to the decimals used to sum the amounts of the CSV file
+fn to_amount(balance: U256, decimals: u8) -> Result<I256> {
+    parse_amount(&format_units(balance, decimals)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        mock_rpc::{self, MockResponse, MockRpc},
+        summary::CurrencyTotals,
+    };
+
+    use alloy::{
+        hex,
+        primitives::address,
+        providers::{ProviderBuilder, RootProvider},
+        transports::http::{Client as HttpClient, Http},
+    };
+    use serde_json::json;
+
+    const WALLET: Address = address!("00000000000000000000000000000000000000aa");
+    const POOL: Address = address!("00000000000000000000000000000000000000c1");
+    const WETH: Address = address!("00000000000000000000000000000000000000c2");
+    const USDC: Address = address!("00000000000000000000000000000000000000c3");
+
+    fn token(address: Address, symbol: &str) -> TokenInfo {
+        TokenInfo {
+            address,
+            symbol: symbol.to_string(),
+            decimals: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn reconciles_tracked_tokens_without_rows() {
+        // The wallet got 5 POOL during the period and always held 3 WETH, but never held USDC.
+        let rpc = MockRpc::start(|method, params, _| match method {
+            "eth_call" => {
+                let to: Address = params[0]["to"].as_str().unwrap().parse().unwrap();
+                let input = params[0]["input"].as_str().or(params[0]["data"].as_str());
+                let block = mock_rpc::quantity(&params[1]);
+
+                assert!(hex::decode(input.unwrap()).unwrap()[4..].ends_with(WALLET.as_slice()));
+
+                let balance = match (to, block) {
+                    (POOL, 9) => 0,
+                    (POOL, _) => 5,
+                    (WETH, _) => 3,
+                    _ => 0,
+                };
+
+                MockResponse::Result(json!(format!("0x{:064x}", balance)))
+            }
+            _ => MockResponse::Error(-32601, format!("unsupported method {}", method)),
+        })
+        .await;
+
+        let provider: RootProvider<Http<HttpClient>, AnyNetwork> = ProviderBuilder::new()
+            .network::<AnyNetwork>()
+            .on_http(rpc.url.clone());
+        let balances = BalanceReader::new(
+            Arc::new(provider),
+            "ETH".to_string(),
+            vec![
+                token(POOL, "POOL"),
+                token(WETH, "WETH"),
+                token(USDC, "USDC"),
+            ],
+            vec![
+                token(POOL, "POOL"),
+                token(WETH, "WETH"),
+                token(USDC, "USDC"),
+            ],
+        );
+
+        let mut summary = Summary::default();
+        summary.currencies.insert(
+            "WETH".to_string(),
+            CurrencyTotals {
+                received: parse_amount("1").unwrap(),
+                ..CurrencyTotals::default()
+            },
+        );
+
+        let reconciliation = balances.reconcile(WALLET, 10, 20, &summary).await.unwrap();
+        let unexplained: Vec<_> = reconciliation
+            .assets
+            .iter()
+            .map(|asset| (asset.symbol.as_str(), asset.unexplained()))
+            .collect();
+
+        assert_eq!(
+            unexplained,
+            vec![
+                ("WETH", -parse_amount("1").unwrap()),
+                ("POOL", parse_amount("5").unwrap())
+            ]
+        );
+    }
+}
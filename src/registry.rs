@@ -0,0 +1,287 @@
+use crate::prices::PriceSource;
+
+use alloy::primitives::{Address, U64};
+use eyre::{eyre, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::Path;
+
+/// Version of the registry file format supported by this program
+pub const REGISTRY_VERSION: u32 = 1;
+
+/// Default registry shipped with the program
+pub const DEFAULT_REGISTRY: &str = include_str!("../registry/default.toml");
+
+/// Registry of the PoolTogether deployments, loaded from a TOML file
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Registry {
+    /// Version of the registry file format
+    pub version: u32,
+    /// Deployments per chain
+    pub chains: Vec<ChainRegistry>,
+}
+
+/// Deployment on a single chain
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChainRegistry {
+    /// Name of the chain
+    pub name: String,
+    /// Chain ID
+    pub chain_id: u64,
+    /// Address of the liquidation router
+    pub liquidation_router: Option<Address>,
+    /// Address of the liquidation pair factory, used to discover liquidation pairs
+    pub liquidation_pair_factory: Option<Address>,
+    /// Block at which the liquidation pair factory was deployed
+    pub liquidation_pair_factory_start_block: Option<u64>,
+    /// Address of the prize token sent to liquidate yield
+    pub prize_token: Address,
+    /// Address of the prize pool, whose prize claims pay fees to claimer bots
+    pub prize_pool: Option<Address>,
+    /// Address of the draw manager, which rewards the bots starting and finishing draws
+    pub draw_manager: Option<Address>,
+    /// Address of the RNG relay auction, which rewards the bots completing the RNG auctions
+    pub rng_relay_auction: Option<Address>,
+    /// Symbol of the token used to pay transaction fees
+    pub fee_token_symbol: String,
+    /// Whether transactions pay an L1 data fee on top of the L2 execution fee
+    #[serde(default)]
+    pub op_stack: bool,
+    /// Liquidation pairs
+    #[serde(default)]
+    pub liquidation_pairs: Vec<LiquidationPairInfo>,
+    /// Tokens, overriding the metadata resolved on-chain
+    #[serde(default)]
+    pub tokens: Vec<TokenInfo>,
+    /// Chainlink feeds and Uniswap V3 pools used to value the rows
+    #[serde(default)]
+    pub price_feeds: Vec<PriceFeedInfo>,
+}
+
+/// Liquidation pair and the underlying asset of the vault it liquidates (i.e. tokenOut underlying asset)
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LiquidationPairInfo {
+    pub address: Address,
+    pub underlying_asset: Address,
+}
+
+/// On-chain price of a currency: a Chainlink feed quoting it in the net worth currency,
+/// or a Uniswap V3 pool pairing it with another currency
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PriceFeedInfo {
+    /// Symbol of the priced currency, as written in the rows
+    pub currency: String,
+    /// `chainlink` or `twap`
+    pub source: PriceSource,
+    pub address: Address,
+}
+
+/// ERC-20 token metadata
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct TokenInfo {
+    pub address: Address,
+    pub symbol: String,
+    pub decimals: u8,
+}
+
+impl Registry {
+    /// Load the registry from `path`, or the default registry if no path is given
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let registry = match path {
+            Some(path) => {
+                let content = std::fs::read_to_string(path).map_err(|error| {
+                    eyre!("Failed to read registry {}: {}", path.display(), error)
+                })?;
+
+                Self::parse(&content)
+                    .map_err(|error| eyre!("Invalid registry {}: {}", path.display(), error))?
+            }
+            None => Self::parse(DEFAULT_REGISTRY)
+                .map_err(|error| eyre!("Invalid default registry: {}", error))?,
+        };
+
+        Ok(registry)
+    }
+
+    /// Parse and validate a registry
+    pub fn parse(content: &str) -> Result<Self> {
+        let registry: Registry = toml::from_str(content)?;
+
+        registry.validate()?;
+
+        Ok(registry)
+    }
+
+    /// Check that the registry is consistent
+    pub fn validate(&self) -> Result<()> {
+        if self.version != REGISTRY_VERSION {
+            return Err(eyre!(
+                "Unsupported registry version {}, expected {}",
+                self.version,
+                REGISTRY_VERSION
+            ));
+        }
+
+        let mut chain_ids = HashSet::new();
+
+        for chain in &self.chains {
+            if !chain_ids.insert(chain.chain_id) {
+                return Err(eyre!("Duplicate chain ID {}", chain.chain_id));
+            }
+
+            chain.validate()?;
+        }
+
+        Ok(())
+    }
+
+    /// Get the deployment for a given chain id
+    pub fn chain(&self, chain_id: U64) -> Result<&ChainRegistry> {
+        self.chains
+            .iter()
+            .find(|chain| U64::from(chain.chain_id) == chain_id)
+            .ok_or_else(|| {
+                eyre!(
+                    "No registry entry found for the given chain ID: {}. The default registry \
+                     only describes Optimism, pass a registry describing this chain with `REGISTRY`",
+                    chain_id
+                )
+            })
+    }
+}
+
+impl ChainRegistry {
+    fn validate(&self) -> Result<()> {
+        let mut tokens = HashSet::new();
+
+        for token in &self.tokens {
+            if !tokens.insert(token.address) {
+                return Err(eyre!(
+                    "Duplicate token {} on chain {}",
+                    token.address,
+                    self.chain_id
+                ));
+            }
+
+            if token.symbol.is_empty() {
+                return Err(eyre!(
+                    "Empty symbol for token {} on chain {}",
+                    token.address,
+                    self.chain_id
+                ));
+            }
+        }
+
+        let mut liquidation_pairs = HashSet::new();
+
+        for liquidation_pair in &self.liquidation_pairs {
+            if !liquidation_pairs.insert(liquidation_pair.address) {
+                return Err(eyre!(
+                    "Duplicate liquidation pair {} on chain {}",
+                    liquidation_pair.address,
+                    self.chain_id
+                ));
+            }
+        }
+
+        let mut price_feeds = HashSet::new();
+
+        for price_feed in &self.price_feeds {
+            if price_feed.source == PriceSource::Csv {
+                return Err(eyre!(
+                    "Invalid source \"csv\" for the price feed of {} on chain {}, expected \"chainlink\" or \"twap\"",
+                    price_feed.currency,
+                    self.chain_id
+                ));
+            }
+
+            if !price_feeds.insert((price_feed.currency.clone(), price_feed.source)) {
+                return Err(eyre!(
+                    "Duplicate {} price feed of {} on chain {}",
+                    price_feed.source,
+                    price_feed.currency,
+                    self.chain_id
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Get the liquidation router address
+    pub fn get_liquidation_router_address(&self) -> Result<Address> {
+        self.liquidation_router.ok_or_else(|| {
+            eyre!(
+                "No liquidation router address found for the given chain ID: {}",
+                self.chain_id
+            )
+        })
+    }
+
+    /// Get the underlying asset address for a given liquidation pair
+    pub fn get_underlying_asset_address(&self, liquidation_pair: Address) -> Result<Address> {
+        self.liquidation_pairs
+            .iter()
+            .find(|pair| pair.address == liquidation_pair)
+            .map(|pair| pair.underlying_asset)
+            .ok_or_else(|| {
+                eyre!(
+                    "No underlying asset address found for the given liquidation pair: {}",
+                    liquidation_pair
+                )
+            })
+    }
+
+    /// Get the metadata of a given token
+    pub fn get_token(&self, asset: Address) -> Result<&TokenInfo> {
+        self.tokens
+            .iter()
+            .find(|token| token.address == asset)
+            .ok_or_else(|| eyre!("No metadata found for the given asset: {}", asset))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_registry_is_valid() {
+        let registry = Registry::load(None).unwrap();
+        let optimism = registry.chain(U64::from(10)).unwrap();
+
+        assert!(optimism.liquidation_router.is_some());
+        assert!(!optimism.liquidation_pairs.is_empty());
+        assert!(optimism.get_token(optimism.prize_token).is_ok());
+    }
+
+    #[test]
+    fn rejects_duplicate_tokens() {
+        let token = r#"
+            [[chains.tokens]]
+            address = "0x0000000000000000000000000000000000000001"
+            symbol = "DAI"
+            decimals = 18
+        "#;
+        let registry = format!(
+            r#"
+            version = 1
+
+            [[chains]]
+            name = "Test"
+            chain_id = 1
+            prize_token = "0x0000000000000000000000000000000000000001"
+            fee_token_symbol = "ETH"
+            {token}
+            {token}
+            "#
+        );
+
+        assert!(Registry::parse(&registry).is_err());
+    }
+}
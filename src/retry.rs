@@ -0,0 +1,263 @@
+use alloy::{
+    rpc::json_rpc::{RequestPacket, ResponsePacket},
+    transports::{
+        layers::{RateLimitRetryPolicy, RetryPolicy},
+        RpcError, TransportError, TransportErrorKind, TransportFut,
+    },
+};
+use rand::Rng;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::{sync::Mutex, time::Instant};
+use tower::{Layer, Service};
+
+/// Retry and rate limiting settings of the RPC requests
+#[derive(Clone, Debug)]
+pub struct RetryConfig {
+    /// Maximum number of attempts per request, including the first one
+    pub max_attempts: u32,
+    /// Backoff before the first retry, doubled after each attempt
+    pub initial_backoff: Duration,
+    /// Upper bound of the backoff between two attempts
+    pub max_backoff: Duration,
+    /// Maximum number of requests sent per second, unlimited if `None`
+    pub requests_per_second: Option<u32>,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            requests_per_second: None,
+        }
+    }
+}
+
+impl RetryConfig {
+    /// Backoff before the given retry, randomized between half and the full exponential backoff
+    fn backoff(&self, retry: u32) -> Duration {
+        let backoff = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_backoff);
+
+        backoff.mul_f64(rand::thread_rng().gen_range(0.5..=1.0))
+    }
+
+    /// Backoff before retrying a failed request, the one requested by the provider if any.
+    /// Both are capped by `max_backoff`, e.g. Infura asks to back off until its daily limit resets.
+    fn retry_backoff(&self, error: &TransportError, retry: u32) -> Duration {
+        match backoff_hint(error) {
+            Some(backoff) => backoff.min(self.max_backoff),
+            None => self.backoff(retry),
+        }
+    }
+}
+
+/// Transport layer retrying failed RPC requests with exponential backoff
+/// and spacing requests to stay under the configured requests per second
+#[derive(Clone, Debug)]
+pub struct RetryLayer {
+    config: RetryConfig,
+    /// Instant at which the next request may be sent, shared by every clone of the service
+    next_request: Arc<Mutex<Instant>>,
+}
+
+impl RetryLayer {
+    pub fn new(config: RetryConfig) -> Self {
+        Self {
+            config,
+            next_request: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+}
+
+impl<S> Layer<S> for RetryLayer {
+    type Service = RetryService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RetryService {
+            inner,
+            config: self.config.clone(),
+            next_request: self.next_request.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RetryService<S> {
+    inner: S,
+    config: RetryConfig,
+    next_request: Arc<Mutex<Instant>>,
+}
+
+impl<S> RetryService<S> {
+    /// Wait until the rate limit allows sending a request
+    async fn wait_for_rate_limit(&self) {
+        let Some(requests_per_second) = self.config.requests_per_second else {
+            return;
+        };
+
+        let interval = Duration::from_secs(1) / requests_per_second.max(1);
+
+        let send_at = {
+            let mut next_request = self.next_request.lock().await;
+            let send_at = (*next_request).max(Instant::now());
+
+            *next_request = send_at + interval;
+
+            send_at
+        };
+
+        tokio::time::sleep_until(send_at).await;
+    }
+}
+
+impl<S> Service<RequestPacket> for RetryService<S>
+where
+    S: Service<RequestPacket, Response = ResponsePacket, Error = TransportError>
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = ResponsePacket;
+    type Error = TransportError;
+    type Future = TransportFut<'static>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: RequestPacket) -> Self::Future {
+        let this = self.clone();
+
+        Box::pin(async move {
+            let mut inner = this.inner.clone();
+            let mut attempt = 1;
+
+            loop {
+                this.wait_for_rate_limit().await;
+
+                // JSON-RPC errors are returned in successful responses, so they are checked separately.
+                let error = match inner.call(request.clone()).await {
+                    Ok(response) => match response.as_error() {
+                        Some(error) => TransportError::ErrorResp(error.clone()),
+                        None => return Ok(response),
+                    },
+                    Err(error) => error,
+                };
+
+                if attempt >= this.config.max_attempts || !is_retryable(&error) {
+                    return Err(error);
+                }
+
+                let backoff = this.config.retry_backoff(&error, attempt - 1);
+
+                log::debug!(
+                    "RPC request failed (attempt {}/{}), retrying in {:?}: {}",
+                    attempt,
+                    this.config.max_attempts,
+                    backoff,
+                    error
+                );
+
+                tokio::time::sleep(backoff).await;
+                attempt += 1;
+            }
+        })
+    }
+}
+
+/// Whether a failed request should be retried: rate limits, server errors and connection failures
+fn is_retryable(error: &TransportError) -> bool {
+    match error {
+        RpcError::Transport(TransportErrorKind::HttpError(error)) => {
+            error.status == 429 || error.status >= 500
+        }
+        RpcError::Transport(TransportErrorKind::Custom(_)) => true,
+        // Infura uses its rate limit error code when `eth_getLogs` returns too many results,
+        // which is not transient and handled by shrinking the block range instead.
+        RpcError::ErrorResp(payload) if payload.message.contains("query returned more than") => {
+            false
+        }
+        error => RateLimitRetryPolicy::default().should_retry(error),
+    }
+}
+
+/// Backoff requested by the provider, if any
+fn backoff_hint(error: &TransportError) -> Option<Duration> {
+    RateLimitRetryPolicy::default().backoff_hint(error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_rpc::{MockResponse, MockRpc};
+
+    use alloy::{
+        network::AnyNetwork,
+        providers::{Provider, ProviderBuilder},
+        rpc::{client::ClientBuilder, json_rpc::ErrorPayload},
+    };
+    use serde_json::json;
+
+    fn config() -> RetryConfig {
+        RetryConfig {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(10),
+            requests_per_second: None,
+        }
+    }
+
+    async fn get_block_number(rpc: &MockRpc, config: RetryConfig) -> Result<u64, TransportError> {
+        let client = ClientBuilder::default()
+            .layer(RetryLayer::new(config))
+            .http(rpc.url.clone());
+
+        ProviderBuilder::new()
+            .network::<AnyNetwork>()
+            .on_client(client)
+            .get_block_number()
+            .await
+    }
+
+    #[tokio::test]
+    async fn retries_rate_limited_requests() {
+        let rpc = MockRpc::start(|_, _, index| match index {
+            0 | 1 => MockResponse::Status(429),
+            _ => MockResponse::Result(json!("0x2a")),
+        })
+        .await;
+
+        assert_eq!(get_block_number(&rpc, config()).await.unwrap(), 42);
+        assert_eq!(rpc.request_count(), 3);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_attempts() {
+        let rpc = MockRpc::start(|_, _, _| MockResponse::Status(429)).await;
+
+        assert!(get_block_number(&rpc, config()).await.is_err());
+        assert_eq!(rpc.request_count(), 3);
+    }
+
+    #[test]
+    fn caps_backoff_requested_by_provider() {
+        let error = TransportError::ErrorResp(
+            serde_json::from_value::<ErrorPayload>(json!({
+                "code": -32005,
+                "message": "daily request count exceeded, request rate limited",
+                "data": {"rate": {"backoff_seconds": 3600}}
+            }))
+            .unwrap(),
+        );
+
+        assert_eq!(config().retry_backoff(&error, 0), Duration::from_millis(10));
+    }
+}
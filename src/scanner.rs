@@ -0,0 +1,380 @@
+use crate::{cache::Cache, log_info_cyan};
+
+use alloy::{
+    network::AnyNetwork,
+    providers::Provider,
+    rpc::types::{BlockNumberOrTag, Filter, Log},
+    transports::{RpcError, Transport, TransportError},
+};
+use colored::Colorize;
+use eyre::{eyre, Result};
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+/// Number of blocks queried by the first `eth_getLogs` request
+const INITIAL_CHUNK_SIZE: u64 = 2000;
+
+/// Number of logs per chunk above which the chunk size is halved.
+/// The chunk size is doubled when a chunk returns less than half of it.
+const TARGET_LOGS_PER_CHUNK: usize = 1000;
+
+/// Fragments of the error messages returned by providers when a `eth_getLogs` request
+/// covers more blocks than they allow
+const BLOCK_RANGE_ERROR_MESSAGES: [&str; 3] =
+    ["block range", "range too large", "range is too large"];
+
+/// Fragments of the error messages returned by providers when a `eth_getLogs` request
+/// returns too many logs, which only depends on the activity of the blocks queried
+const RESULT_ERROR_MESSAGES: [&str; 5] = [
+    "query returned more than",
+    "too many results",
+    "too many logs",
+    "response size",
+    "query timeout",
+];
+
+/// Reason of a `eth_getLogs` request rejected by the provider
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum RangeError {
+    /// The request covers more blocks than the provider allows
+    BlockRange,
+    /// The request returns too many logs
+    Results,
+}
+
+/// Bounds of the number of blocks queried at once
+#[derive(Clone, Copy, Debug)]
+pub struct ChunkConfig {
+    pub min_chunk_size: u64,
+    pub max_chunk_size: u64,
+}
+
+impl Default for ChunkConfig {
+    fn default() -> Self {
+        Self {
+            min_chunk_size: 10,
+            max_chunk_size: 50_000,
+        }
+    }
+}
+
+/// Logs of an inclusive range of blocks
+pub struct LogChunk {
+    pub from_block: u64,
+    pub to_block: u64,
+    pub logs: Vec<Log>,
+}
+
+/// Statistics of the chunks queried by a scanner
+#[derive(Debug, Default)]
+pub struct ChunkStats {
+    /// Number of successful `eth_getLogs` requests
+    pub chunks: u64,
+    /// Number of requests rejected by the provider and retried with a smaller range
+    pub rejected_chunks: u64,
+    /// Number of chunks whose logs were all read from the cache
+    pub cached_chunks: u64,
+    /// Number of logs returned
+    pub logs: u64,
+    /// Smallest and largest number of blocks of a successful request
+    pub min_chunk_size: Option<u64>,
+    pub max_chunk_size: Option<u64>,
+}
+
+impl ChunkStats {
+    fn record(&mut self, chunk_size: u64, logs: usize) {
+        self.chunks += 1;
+        self.logs += logs as u64;
+        self.min_chunk_size = Some(
+            self.min_chunk_size
+                .map_or(chunk_size, |min| min.min(chunk_size)),
+        );
+        self.max_chunk_size = Some(
+            self.max_chunk_size
+                .map_or(chunk_size, |max| max.max(chunk_size)),
+        );
+    }
+
+    /// Log the statistics
+    pub fn report(&self) {
+        log_info_cyan!(
+            "Queried {} logs in {} chunks of {} to {} blocks, {} chunks read from the cache, {} chunks rejected by the provider",
+            self.logs,
+            self.chunks,
+            self.min_chunk_size.unwrap_or_default(),
+            self.max_chunk_size.unwrap_or_default(),
+            self.cached_chunks,
+            self.rejected_chunks
+        );
+    }
+}
+
+/// Queries the logs matching any of a set of filters over a range of blocks, growing the number of blocks queried
+/// at once on quiet ranges and shrinking it on busy ones or when the provider rejects the request.
+/// A range rejected for its number of blocks also lowers the maximum chunk size so that it is not tried again.
+pub struct LogScanner<T, P> {
+    /// Provider
+    provider: Arc<P>,
+    /// Filters of the logs, whose block range is overridden for each chunk
+    filters: Vec<Filter>,
+    /// On-disk cache of the chunks
+    cache: Arc<Cache>,
+    /// Bounds of the chunk size
+    config: ChunkConfig,
+    /// Number of blocks of the next request
+    chunk_size: u64,
+    /// First block of the next chunk
+    next_block: u64,
+    /// Last block to query (inclusive)
+    to_block: u64,
+    pub stats: ChunkStats,
+    phantom: PhantomData<T>,
+}
+
+impl<T, P> LogScanner<T, P>
+where
+    T: Transport + Clone,
+    P: Provider<T, AnyNetwork>,
+{
+    pub fn new(
+        provider: Arc<P>,
+        filters: Vec<Filter>,
+        cache: Arc<Cache>,
+        from_block: u64,
+        to_block: u64,
+        config: ChunkConfig,
+    ) -> Self {
+        Self {
+            provider,
+            filters,
+            cache,
+            config,
+            chunk_size: INITIAL_CHUNK_SIZE.clamp(config.min_chunk_size, config.max_chunk_size),
+            next_block: from_block,
+            to_block,
+            stats: ChunkStats::default(),
+            phantom: PhantomData,
+        }
+    }
+
+    /// Query the logs of the next chunk, `None` once the whole range has been scanned
+    pub async fn next_chunk(&mut self) -> Result<Option<LogChunk>> {
+        if self.next_block > self.to_block {
+            return Ok(None);
+        }
+
+        loop {
+            let from_block = self.next_block;
+            let to_block = std::cmp::min(from_block + self.chunk_size - 1, self.to_block);
+
+            match self.get_logs(from_block, to_block).await {
+                Ok(logs) => return Ok(Some(self.accept_chunk(from_block, to_block, logs))),
+                Err(error) if self.chunk_size > self.config.min_chunk_size => {
+                    let Some(range_error) = range_error(&error) else {
+                        return Err(eyre!(
+                            "Failed to get logs of blocks {} to {}: {}",
+                            from_block,
+                            to_block,
+                            error
+                        ));
+                    };
+
+                    log::debug!(
+                        "Provider rejected blocks {} to {}, shrinking the range: {}",
+                        from_block,
+                        to_block,
+                        error
+                    );
+
+                    self.stats.rejected_chunks += 1;
+                    self.shrink();
+
+                    // Busy ranges only shrink the current chunk, which grows again on quieter ones.
+                    if range_error == RangeError::BlockRange {
+                        self.config.max_chunk_size = self.chunk_size;
+                    }
+                }
+                Err(error) => {
+                    return Err(eyre!(
+                        "Failed to get logs of blocks {} to {}: {}",
+                        from_block,
+                        to_block,
+                        error
+                    ))
+                }
+            }
+        }
+    }
+
+    /// Query the logs of every filter over a range of blocks, logs matching several filters
+    /// being only returned once
+    async fn get_logs(
+        &mut self,
+        from_block: u64,
+        to_block: u64,
+    ) -> Result<Vec<Log>, TransportError> {
+        let mut logs = Vec::new();
+        let mut cached = true;
+
+        for filter in &self.filters {
+            // Chunks are cached with their block range, which only depends on the logs returned
+            // by the previous chunks, so a rerun over the same range reads the same chunks.
+            if let Some(filter_logs) = self.cache.get_logs(filter, from_block, to_block) {
+                logs.extend(filter_logs);
+                continue;
+            }
+
+            let filter_logs = self
+                .provider
+                .get_logs(
+                    &filter
+                        .clone()
+                        .from_block(BlockNumberOrTag::Number(from_block))
+                        .to_block(BlockNumberOrTag::Number(to_block)),
+                )
+                .await?;
+
+            self.cache
+                .put_logs(filter, from_block, to_block, &filter_logs);
+
+            logs.extend(filter_logs);
+            cached = false;
+        }
+
+        if cached {
+            self.stats.cached_chunks += 1;
+        }
+
+        if self.filters.len() > 1 {
+            logs.sort_by_key(|log| (log.block_number, log.log_index));
+            logs.dedup_by_key(|log| (log.block_number, log.log_index));
+        }
+
+        Ok(logs)
+    }
+
+    /// Record a chunk and adapt the size of the next one to its number of logs
+    fn accept_chunk(&mut self, from_block: u64, to_block: u64, logs: Vec<Log>) -> LogChunk {
+        self.stats.record(to_block - from_block + 1, logs.len());
+        self.next_block = to_block + 1;
+
+        if logs.len() > TARGET_LOGS_PER_CHUNK {
+            self.shrink();
+        } else if logs.len() < TARGET_LOGS_PER_CHUNK / 2 {
+            self.chunk_size = (self.chunk_size * 2).min(self.config.max_chunk_size);
+        }
+
+        LogChunk {
+            from_block,
+            to_block,
+            logs,
+        }
+    }
+
+    fn shrink(&mut self) {
+        self.chunk_size = (self.chunk_size / 2).max(self.config.min_chunk_size);
+    }
+}
+
+/// Why the provider rejected the request, `None` if it failed for another reason
+fn range_error(error: &TransportError) -> Option<RangeError> {
+    let message = match error {
+        RpcError::ErrorResp(payload) => payload.message.to_lowercase(),
+        error => error.to_string().to_lowercase(),
+    };
+    let matches = |fragments: &[&str]| fragments.iter().any(|fragment| message.contains(fragment));
+
+    if matches(&BLOCK_RANGE_ERROR_MESSAGES) {
+        Some(RangeError::BlockRange)
+    } else if matches(&RESULT_ERROR_MESSAGES) {
+        Some(RangeError::Results)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_rpc::{MockResponse, MockRpc};
+
+    use alloy::{
+        providers::{ProviderBuilder, RootProvider},
+        transports::http::{Client as HttpClient, Http},
+    };
+    use serde_json::json;
+    use std::path::Path;
+
+    type MockTransport = Http<HttpClient>;
+    type MockProvider = RootProvider<MockTransport, AnyNetwork>;
+
+    /// Scanner over blocks 0 to 99999 whose first `eth_getLogs` request is rejected with `message`
+    async fn scanner(message: &'static str) -> LogScanner<MockTransport, MockProvider> {
+        let rpc = MockRpc::start(move |method, _, index| match (method, index) {
+            ("eth_getLogs", 0) => MockResponse::Error(-32005, message.to_string()),
+            ("eth_getLogs", _) => MockResponse::Result(json!([])),
+            (method, _) => panic!("Unexpected request {}", method),
+        })
+        .await;
+
+        let provider = ProviderBuilder::new()
+            .network::<AnyNetwork>()
+            .on_http(rpc.url.clone());
+
+        LogScanner::new(
+            Arc::new(provider),
+            vec![Filter::new()],
+            Arc::new(Cache::new(Path::new("unused"), 1)),
+            0,
+            99_999,
+            ChunkConfig::default(),
+        )
+    }
+
+    /// Number of blocks of the first chunks
+    async fn chunk_sizes(scanner: &mut LogScanner<MockTransport, MockProvider>) -> Vec<u64> {
+        let mut sizes = Vec::new();
+
+        for _ in 0..3 {
+            let chunk = scanner.next_chunk().await.unwrap().unwrap();
+            sizes.push(chunk.to_block - chunk.from_block + 1);
+        }
+
+        sizes
+    }
+
+    #[tokio::test]
+    async fn caps_chunk_size_on_block_range_errors() {
+        let mut scanner = scanner("block range is too wide").await;
+
+        assert_eq!(chunk_sizes(&mut scanner).await, vec![1000, 1000, 1000]);
+        assert_eq!(scanner.stats.rejected_chunks, 1);
+    }
+
+    #[tokio::test]
+    async fn grows_chunk_size_again_after_too_many_results() {
+        let mut scanner = scanner("query returned more than 10000 results").await;
+
+        assert_eq!(chunk_sizes(&mut scanner).await, vec![1000, 2000, 4000]);
+        assert_eq!(scanner.stats.rejected_chunks, 1);
+    }
+
+    #[test]
+    fn classifies_range_errors() {
+        let error = |message: &str| {
+            TransportError::ErrorResp(
+                serde_json::from_value(json!({"code": -32000, "message": message})).unwrap(),
+            )
+        };
+
+        assert_eq!(
+            range_error(&error("Log response size exceeded")),
+            Some(RangeError::Results)
+        );
+        assert_eq!(
+            range_error(&error("exceed maximum block range: 50000")),
+            Some(RangeError::BlockRange)
+        );
+        assert_eq!(range_error(&error("execution reverted")), None);
+    }
+}
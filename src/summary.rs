@@ -0,0 +1,122 @@
+use alloy::primitives::{
+    utils::{format_units, parse_units},
+    I256,
+};
+use eyre::{eyre, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Decimals used to sum amounts read back from the CSV, enough for any ERC-20 token
+pub const AMOUNT_DECIMALS: u8 = 18;
+
+/// Columns of a Koinly CSV row needed to compute totals
+#[derive(Deserialize)]
+struct KoinlyRow {
+    #[serde(rename = "Sent Amount")]
+    amount_in: String,
+    #[serde(rename = "Sent Currency")]
+    amount_in_symbol: String,
+    #[serde(rename = "Received Amount")]
+    amount_out: String,
+    #[serde(rename = "Received Currency")]
+    amount_out_symbol: String,
+    #[serde(rename = "Fee Amount")]
+    fee: String,
+    #[serde(rename = "Fee Currency")]
+    fee_symbol: String,
+    /// Only present in files combining several wallets
+    #[serde(rename = "Wallet", default)]
+    wallet: Option<String>,
+}
+
+/// Totals of a single currency in a Koinly CSV file
+#[derive(Clone, Default)]
+pub struct CurrencyTotals {
+    pub sent: I256,
+    pub received: I256,
+    pub fees: I256,
+}
+
+impl CurrencyTotals {
+    /// Net amount of the currency received by the wallet
+    pub fn net(&self) -> I256 {
+        self.received - self.sent - self.fees
+    }
+}
+
+/// Summary of a Koinly CSV file
+#[derive(Default)]
+pub struct Summary {
+    /// Number of rows in the file
+    pub rows: usize,
+    /// Totals per currency symbol
+    pub currencies: BTreeMap<String, CurrencyTotals>,
+}
+
+impl Summary {
+    /// Read a Koinly CSV file and sum its amounts per currency
+    pub fn from_koinly_csv(path: &Path) -> Result<Self> {
+        Self::from_koinly_csv_for_wallet(path, None)
+    }
+
+    /// Read a Koinly CSV file and sum the amounts per currency of the rows of a given wallet,
+    /// matched against the Wallet column of files combining several wallets
+    pub fn from_koinly_csv_for_wallet(path: &Path, wallet: Option<&str>) -> Result<Self> {
+        let mut reader = csv::Reader::from_path(path)
+            .map_err(|error| eyre!("Failed to open {}: {}", path.display(), error))?;
+
+        let mut summary = Summary::default();
+
+        for row in reader.deserialize() {
+            let row: KoinlyRow = row?;
+
+            if wallet.is_some() && row.wallet.as_deref() != wallet {
+                continue;
+            }
+
+            summary.rows += 1;
+            summary.add(&row.amount_in_symbol, &row.amount_in, |totals| {
+                &mut totals.sent
+            })?;
+            summary.add(&row.amount_out_symbol, &row.amount_out, |totals| {
+                &mut totals.received
+            })?;
+            summary.add(&row.fee_symbol, &row.fee, |totals| &mut totals.fees)?;
+        }
+
+        Ok(summary)
+    }
+
+    fn add(
+        &mut self,
+        symbol: &str,
+        amount: &str,
+        field: impl Fn(&mut CurrencyTotals) -> &mut I256,
+    ) -> Result<()> {
+        if symbol.is_empty() || amount.is_empty() {
+            return Ok(());
+        }
+
+        *field(self.currencies.entry(symbol.to_string()).or_default()) += parse_amount(amount)?;
+
+        Ok(())
+    }
+}
+
+/// Parse a decimal amount written in the CSV
+pub fn parse_amount(amount: &str) -> Result<I256> {
+    Ok(parse_units(amount, AMOUNT_DECIMALS)
+        .map_err(|_| eyre!("Failed to parse amount \"{}\"", amount))?
+        .get_signed())
+}
+
+/// Format an amount parsed with `parse_amount`, without trailing zeros
+pub fn format_amount(amount: I256) -> String {
+    let formatted = format_units(amount, AMOUNT_DECIMALS).unwrap_or_default();
+
+    formatted
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_string()
+}
@@ -0,0 +1,181 @@
+use crate::{
+    cache::{Cache, CacheKind},
+    helpers::{push_unique, save_cache},
+    log_info_cyan,
+    registry::{ChainRegistry, TokenInfo},
+};
+
+use alloy::{
+    network::AnyNetwork,
+    primitives::{Address, FixedBytes},
+    providers::Provider,
+    sol,
+    transports::Transport,
+};
+use colored::Colorize;
+use eyre::{eyre, Result};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+sol! {
+    #[sol(rpc)]
+    interface IERC20Metadata {
+        function symbol() external view returns (string);
+        function decimals() external view returns (uint8);
+    }
+
+    /// Tokens deployed before the ERC-20 metadata extension (e.g. MKR) return their symbol as bytes32
+    #[sol(rpc)]
+    interface IERC20MetadataBytes32 {
+        function symbol() external view returns (bytes32);
+    }
+}
+
+/// Resolves token symbols and decimals, looking them up in the registry first
+/// and falling back to on-chain calls for unknown tokens. Tokens resolved on-chain are cached on disk.
+pub struct TokenResolver<T, P> {
+    /// Provider
+    provider: Arc<P>,
+    /// Registry of the chain, whose tokens override the ones resolved on-chain
+    registry: ChainRegistry,
+    /// Path of the cache file
+    cache_path: PathBuf,
+    /// Tokens resolved on-chain
+    cache: Mutex<Vec<TokenInfo>>,
+    phantom: PhantomData<T>,
+}
+
+impl<T, P> TokenResolver<T, P>
+where
+    T: Transport + Clone,
+    P: Provider<T, AnyNetwork>,
+{
+    pub fn new(provider: Arc<P>, registry: ChainRegistry, cache_dir: &Path) -> Result<Self> {
+        let cache_path = Cache::resolved_path(cache_dir, registry.chain_id, CacheKind::Tokens);
+
+        let cache = match std::fs::read_to_string(&cache_path) {
+            Ok(content) => serde_json::from_str(&content).map_err(|error| {
+                eyre!("Failed to parse cache {}: {}", cache_path.display(), error)
+            })?,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(error) => {
+                return Err(eyre!(
+                    "Failed to read cache {}: {}",
+                    cache_path.display(),
+                    error
+                ))
+            }
+        };
+
+        Ok(Self {
+            provider,
+            registry,
+            cache_path,
+            cache: Mutex::new(cache),
+            phantom: PhantomData,
+        })
+    }
+
+    /// Get the symbol and decimals of a given token
+    pub async fn get_token(&self, asset: Address) -> Result<TokenInfo> {
+        if let Ok(token) = self.registry.get_token(asset) {
+            return Ok(token.clone());
+        }
+
+        let cached_token = self
+            .cache
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|token| token.address == asset)
+            .cloned();
+
+        match cached_token {
+            Some(token) => Ok(token),
+            None => self.resolve_token(asset).await,
+        }
+    }
+
+    /// Tokens of the registry and tokens resolved on-chain so far, the registry taking precedence
+    pub fn known_tokens(&self) -> Vec<TokenInfo> {
+        let mut tokens = self.registry.tokens.clone();
+
+        for token in self.cache.lock().unwrap().iter() {
+            push_unique(&mut tokens, token.clone(), |token| token.address);
+        }
+
+        tokens
+    }
+
+    /// Read the symbol and decimals of a token on-chain
+    async fn resolve_token(&self, asset: Address) -> Result<TokenInfo> {
+        let decimals = IERC20Metadata::new(asset, self.provider.as_ref())
+            .decimals()
+            .call()
+            .await
+            .map_err(|error| eyre!("Failed to get decimals of token {}: {}", asset, error))?
+            ._0;
+
+        let token = TokenInfo {
+            address: asset,
+            symbol: self.get_symbol(asset).await?,
+            decimals,
+        };
+
+        log_info_cyan!("Resolved token {} ({})", token.symbol, asset);
+
+        push_unique(&mut self.cache.lock().unwrap(), token.clone(), |token| {
+            token.address
+        });
+        save_cache(&self.cache_path, &self.cache)?;
+
+        Ok(token)
+    }
+
+    /// Read the symbol of a token, returned either as a string or as bytes32
+    async fn get_symbol(&self, asset: Address) -> Result<String> {
+        if let Ok(symbol) = IERC20Metadata::new(asset, self.provider.as_ref())
+            .symbol()
+            .call()
+            .await
+        {
+            return Ok(symbol._0);
+        }
+
+        let symbol = IERC20MetadataBytes32::new(asset, self.provider.as_ref())
+            .symbol()
+            .call()
+            .await
+            .map_err(|error| eyre!("Failed to get symbol of token {}: {}", asset, error))?
+            ._0;
+
+        bytes32_to_string(symbol).ok_or_else(|| eyre!("Failed to decode symbol of token {}", asset))
+    }
+}
+
+/// Decode a null padded bytes32 string
+fn bytes32_to_string(bytes: FixedBytes<32>) -> Option<String> {
+    let length = bytes.iter().position(|byte| *byte == 0).unwrap_or(32);
+    let symbol = std::str::from_utf8(&bytes[..length]).ok()?;
+
+    (!symbol.is_empty()).then(|| symbol.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_bytes32_symbols() {
+        let mut bytes = [0u8; 32];
+        bytes[..3].copy_from_slice(b"MKR");
+
+        assert_eq!(bytes32_to_string(bytes.into()), Some("MKR".to_string()));
+        assert_eq!(bytes32_to_string([b'A'; 32].into()), Some("A".repeat(32)));
+        assert_eq!(bytes32_to_string(FixedBytes::ZERO), None);
+
+        bytes[..2].copy_from_slice(&[0xff, 0xfe]);
+        assert_eq!(bytes32_to_string(bytes.into()), None);
+    }
+}
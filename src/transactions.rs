@@ -0,0 +1,110 @@
+use alloy::primitives::{Address, TxHash};
+use eyre::{eyre, Result};
+use foundry_block_explorers::{
+    account::{GenesisOption, Sort, TxListParams},
+    Client,
+};
+use std::collections::HashSet;
+
+/// Maximum number of transactions returned by an Etherscan `txlist` request
+const TXLIST_PAGE_SIZE: u64 = 10_000;
+
+/// Transaction sent by a wallet to the liquidation router that Etherscan reports as failed
+#[derive(Clone, Debug)]
+pub struct FailedTransaction {
+    pub block_number: u64,
+    pub transaction_index: u64,
+    pub tx_hash: TxHash,
+    /// Wallet that sent the transaction
+    pub wallet: Address,
+}
+
+/// Get the failed transactions sent by the wallets to a contract over an inclusive range of blocks,
+/// sorted by block and transaction index
+pub async fn get_failed_transactions(
+    etherscan: &Client,
+    wallets: &[Address],
+    to: Address,
+    from_block: u64,
+    to_block: u64,
+) -> Result<Vec<FailedTransaction>> {
+    let mut transactions = Vec::new();
+
+    for wallet in wallets {
+        let mut seen = HashSet::new();
+        let mut start_block = from_block;
+
+        // Etherscan returns at most one page of transactions, the next page starts at the block
+        // of the last transaction returned, whose transactions are skipped if already seen.
+        loop {
+            let page = etherscan
+                .get_transactions(
+                    wallet,
+                    Some(TxListParams::new(
+                        start_block,
+                        to_block,
+                        1,
+                        TXLIST_PAGE_SIZE,
+                        Sort::Asc,
+                    )),
+                )
+                .await
+                .map_err(|error| {
+                    eyre!(
+                        "Failed to list the transactions of {} from block {}: {}",
+                        wallet,
+                        start_block,
+                        error
+                    )
+                })?;
+
+            let last_block = page
+                .last()
+                .and_then(|transaction| transaction.block_number.as_number());
+
+            for transaction in &page {
+                let GenesisOption::Some(tx_hash) = transaction.hash else {
+                    continue;
+                };
+
+                let failed = transaction.is_error == "1" || transaction.tx_receipt_status == "0";
+
+                if seen.insert(tx_hash) && failed && transaction.to == Some(to) {
+                    transactions.push(FailedTransaction {
+                        block_number: transaction
+                            .block_number
+                            .as_number()
+                            .ok_or_else(|| {
+                                eyre!("Missing block number of transaction {}", tx_hash)
+                            })?
+                            .to(),
+                        transaction_index: transaction.transaction_index.unwrap_or_default(),
+                        tx_hash,
+                        wallet: *wallet,
+                    });
+                }
+            }
+
+            match last_block.map(|block| block.to::<u64>()) {
+                Some(last_block) if page.len() as u64 == TXLIST_PAGE_SIZE => {
+                    if last_block == start_block {
+                        return Err(eyre!(
+                            "More than {} transactions of {} in block {}",
+                            TXLIST_PAGE_SIZE,
+                            wallet,
+                            start_block
+                        ));
+                    }
+
+                    start_block = last_block;
+                }
+                _ => break,
+            }
+        }
+    }
+
+    transactions
+        .sort_by_key(|transaction| (transaction.block_number, transaction.transaction_index));
+
+    Ok(transactions)
+}
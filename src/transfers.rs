@@ -0,0 +1,334 @@
+use alloy::{
+    primitives::{Address, TxHash, I256, U256},
+    rpc::types::AnyTransactionReceipt,
+    sol,
+    sol_types::SolEvent,
+};
+use serde::{Deserialize, Serialize};
+
+sol! {
+    event Transfer(address indexed from, address indexed to, uint256 value);
+}
+
+/// Amounts of tokens, keyed by token address
+pub type TokenAmounts = Vec<(Address, U256)>;
+
+/// Amounts of each token received and sent by a wallet in a transaction, read from the ERC-20
+/// `Transfer` logs of its receipt
+pub struct TokenFlows {
+    /// Token, amount received and amount sent, in the order of their first transfer
+    flows: Vec<(Address, U256, U256)>,
+}
+
+impl TokenFlows {
+    /// Read the flows of a wallet, skipping the `Transfer` logs that fail to be decoded,
+    /// e.g. emitted by non-standard tokens, so that they don't fail the whole transaction
+    pub fn from_receipt(receipt: &AnyTransactionReceipt, wallet: Address) -> Self {
+        let mut flows: Vec<(Address, U256, U256)> = Vec::new();
+
+        for log in receipt.inner.inner.logs() {
+            // ERC-721 transfers share the signature but index the token ID as a fourth topic.
+            if log.topics().first() != Some(&Transfer::SIGNATURE_HASH) || log.topics().len() != 3 {
+                continue;
+            }
+
+            let transfer = match log.log_decode::<Transfer>() {
+                Ok(transfer) => transfer.inner.data,
+                Err(error) => {
+                    log::debug!(
+                        "Skipping Transfer log {:?} of token {}: {}",
+                        log.log_index,
+                        log.address(),
+                        error
+                    );
+                    continue;
+                }
+            };
+
+            if transfer.from != wallet && transfer.to != wallet {
+                continue;
+            }
+
+            let token = log.address();
+            let index = match flows.iter().position(|(address, _, _)| *address == token) {
+                Some(index) => index,
+                None => {
+                    flows.push((token, U256::ZERO, U256::ZERO));
+                    flows.len() - 1
+                }
+            };
+
+            if transfer.to == wallet {
+                flows[index].1 += transfer.value;
+            }
+
+            if transfer.from == wallet {
+                flows[index].2 += transfer.value;
+            }
+        }
+
+        Self { flows }
+    }
+
+    /// Whether no token was transferred from or to the wallet
+    pub fn is_empty(&self) -> bool {
+        self.flows.is_empty()
+    }
+
+    /// Net amount of a token received by the wallet, negative if more was sent
+    pub fn net_received(&self, token: Address) -> I256 {
+        self.flows
+            .iter()
+            .find(|(address, _, _)| *address == token)
+            .map_or(I256::ZERO, |(_, received, sent)| {
+                I256::from_raw(*received) - I256::from_raw(*sent)
+            })
+    }
+
+    /// Tokens whose net amount sent by the wallet differs from the expected one, as
+    /// (token, expected amount sent, transferred amount sent), amounts received being negative
+    pub fn unexpected_transfers(&self, expected: &[(Address, I256)]) -> Vec<(Address, I256, I256)> {
+        expected
+            .iter()
+            .map(|(token, expected_sent)| (*token, *expected_sent, -self.net_received(*token)))
+            .filter(|(_, expected_sent, transferred_sent)| transferred_sent != expected_sent)
+            .collect()
+    }
+
+    /// Tokens of which more was sent than received, and tokens of which more was received than
+    /// sent, with their net amounts
+    pub fn net_transfers(&self) -> (TokenAmounts, TokenAmounts) {
+        let sent = self
+            .flows
+            .iter()
+            .filter(|(_, received, sent)| sent > received)
+            .map(|(token, received, sent)| (*token, sent - received))
+            .collect();
+        let received = self
+            .flows
+            .iter()
+            .filter(|(_, received, sent)| received > sent)
+            .map(|(token, received, sent)| (*token, received - sent))
+            .collect();
+
+        (sent, received)
+    }
+}
+
+/// Amounts sent and received by a row of net transfers, the received one being absent for
+/// a cost and the sent one for an income
+pub type NetTransferRow = (Option<(Address, U256)>, Option<(Address, U256)>);
+
+/// Pair the net amounts sent and received by a wallet into rows. A single token sent is traded
+/// for each token received, its amount being split evenly between the trades and the remainder
+/// added to the first one. With several tokens sent, or none, which token paid for which is
+/// unknown, so each token received is recorded as an income and each token sent as a cost.
+/// Rows follow the order of the amounts.
+pub fn pair_net_transfers(sent: &TokenAmounts, received: &TokenAmounts) -> Vec<NetTransferRow> {
+    match sent.as_slice() {
+        [(token, amount)] if !received.is_empty() => {
+            let count = U256::from(received.len());
+            let share = amount / count;
+            let remainder = amount % count;
+
+            received
+                .iter()
+                .enumerate()
+                .map(|(index, received)| {
+                    let sent = if index == 0 { share + remainder } else { share };
+
+                    (Some((*token, sent)), Some(*received))
+                })
+                .collect()
+        }
+        _ => received
+            .iter()
+            .map(|received| (None, Some(*received)))
+            .chain(sent.iter().map(|sent| (Some(*sent), None)))
+            .collect(),
+    }
+}
+
+/// Liquidation whose decoded amount differs from the net amount transferred by its wallet, e.g.
+/// with fee-on-transfer tokens
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TransferMismatch {
+    #[serde(rename = "Block")]
+    pub block_number: u64,
+    #[serde(rename = "Log Index")]
+    pub log_index: Option<u64>,
+    #[serde(rename = "TxHash")]
+    pub tx_hash: TxHash,
+    #[serde(rename = "Wallet")]
+    pub wallet: Address,
+    #[serde(rename = "Token")]
+    pub token: Address,
+    #[serde(rename = "Currency")]
+    pub symbol: String,
+    /// Amount sent or received according to the liquidation event
+    #[serde(rename = "Event Amount")]
+    pub event_amount: String,
+    /// Net amount sent or received according to the transfers, negative if it went the other way
+    #[serde(rename = "Transferred Amount")]
+    pub transferred_amount: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use alloy::primitives::{address, B256};
+    use serde_json::{json, Value};
+
+    const WALLET: Address = address!("00000000000000000000000000000000000000aa");
+    const OTHER: Address = address!("00000000000000000000000000000000000000bb");
+    const POOL: Address = address!("00000000000000000000000000000000000000c1");
+    const WETH: Address = address!("00000000000000000000000000000000000000c2");
+
+    /// `Transfer` log of a token with a raw data field
+    fn transfer_log(token: Address, from: Address, to: Address, data: String) -> Value {
+        json!({
+            "address": token,
+            "topics": [Transfer::SIGNATURE_HASH, from.into_word(), to.into_word()],
+            "data": data,
+            "blockNumber": "0x1",
+            "blockHash": B256::ZERO,
+            "transactionHash": B256::ZERO,
+            "transactionIndex": "0x0",
+            "logIndex": "0x0",
+            "removed": false
+        })
+    }
+
+    /// `Transfer` log of an amount of a token
+    fn transfer(token: Address, from: Address, to: Address, value: u64) -> Value {
+        transfer_log(token, from, to, format!("0x{:064x}", value))
+    }
+
+    /// Receipt of a successful transaction sent by the wallet with the given logs
+    fn receipt(logs: Vec<Value>) -> AnyTransactionReceipt {
+        serde_json::from_value(json!({
+            "type": "0x2",
+            "status": "0x1",
+            "cumulativeGasUsed": "0x1",
+            "logs": logs,
+            "logsBloom": format!("0x{}", "00".repeat(256)),
+            "transactionHash": B256::ZERO,
+            "transactionIndex": "0x0",
+            "blockHash": B256::ZERO,
+            "blockNumber": "0x1",
+            "gasUsed": "0x1",
+            "effectiveGasPrice": "0x1",
+            "from": WALLET,
+            "to": OTHER,
+            "contractAddress": null
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn nets_transfers_of_the_wallet() {
+        let flows = TokenFlows::from_receipt(
+            &receipt(vec![
+                transfer(POOL, WALLET, OTHER, 100),
+                transfer(WETH, OTHER, WALLET, 7),
+                transfer(POOL, OTHER, WALLET, 30),
+                transfer(WETH, OTHER, POOL, 1000),
+            ]),
+            WALLET,
+        );
+
+        assert_eq!(flows.net_received(POOL), I256::try_from(-70).unwrap());
+        assert_eq!(flows.net_received(WETH), I256::try_from(7).unwrap());
+        assert_eq!(
+            flows.net_transfers(),
+            (vec![(POOL, U256::from(70))], vec![(WETH, U256::from(7))])
+        );
+    }
+
+    #[test]
+    fn pairs_a_single_token_sent_with_each_token_received() {
+        const SHARES: Address = address!("00000000000000000000000000000000000000c3");
+
+        let amount = |token, value: u64| (token, U256::from(value));
+
+        assert_eq!(
+            pair_net_transfers(&vec![amount(POOL, 100)], &vec![amount(WETH, 7)]),
+            vec![(Some(amount(POOL, 100)), Some(amount(WETH, 7)))]
+        );
+        assert_eq!(
+            pair_net_transfers(
+                &vec![amount(POOL, 100)],
+                &vec![amount(WETH, 7), amount(SHARES, 5), amount(OTHER, 1)]
+            ),
+            vec![
+                (Some(amount(POOL, 34)), Some(amount(WETH, 7))),
+                (Some(amount(POOL, 33)), Some(amount(SHARES, 5))),
+                (Some(amount(POOL, 33)), Some(amount(OTHER, 1)))
+            ]
+        );
+
+        // Which of several tokens sent paid for a token received is unknown.
+        assert_eq!(
+            pair_net_transfers(
+                &vec![amount(POOL, 100), amount(SHARES, 5)],
+                &vec![amount(WETH, 7)]
+            ),
+            vec![
+                (None, Some(amount(WETH, 7))),
+                (Some(amount(POOL, 100)), None),
+                (Some(amount(SHARES, 5)), None)
+            ]
+        );
+        assert_eq!(
+            pair_net_transfers(&vec![amount(POOL, 100)], &Vec::new()),
+            vec![(Some(amount(POOL, 100)), None)]
+        );
+        assert_eq!(
+            pair_net_transfers(&Vec::new(), &vec![amount(POOL, 30)]),
+            vec![(None, Some(amount(POOL, 30)))]
+        );
+    }
+
+    #[test]
+    fn reports_unexpected_transfers() {
+        const SHARES: Address = address!("00000000000000000000000000000000000000c3");
+
+        // The wallet paid 100 POOL, of which 2 were taken as a transfer fee, for 7 vault shares.
+        let flows = TokenFlows::from_receipt(
+            &receipt(vec![
+                transfer(POOL, WALLET, OTHER, 98),
+                transfer(POOL, WALLET, POOL, 2),
+                transfer(SHARES, OTHER, WALLET, 7),
+            ]),
+            WALLET,
+        );
+
+        let amount = |value: i64| I256::try_from(value).unwrap();
+
+        assert!(flows
+            .unexpected_transfers(&[(POOL, amount(100)), (SHARES, amount(-7))])
+            .is_empty());
+        assert_eq!(
+            flows.unexpected_transfers(&[(POOL, amount(101)), (WETH, amount(-7))]),
+            vec![
+                (POOL, amount(101), amount(100)),
+                (WETH, amount(-7), I256::ZERO)
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_transfers_that_fail_to_decode() {
+        let flows = TokenFlows::from_receipt(
+            &receipt(vec![
+                transfer_log(POOL, WALLET, OTHER, "0x".to_string()),
+                transfer(WETH, OTHER, WALLET, 7),
+            ]),
+            WALLET,
+        );
+
+        assert_eq!(flows.net_received(POOL), I256::ZERO);
+        assert_eq!(flows.net_received(WETH), I256::try_from(7).unwrap());
+    }
+}
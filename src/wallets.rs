@@ -0,0 +1,304 @@
+use alloy::{primitives::Address, rpc::types::Filter};
+use eyre::{eyre, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::str::FromStr;
+
+/// Wallet whose liquidations are exported, with an optional label
+/// used in file names and in the Wallet column instead of its address
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Wallet {
+    pub address: Address,
+    pub label: Option<String>,
+}
+
+impl Wallet {
+    /// Label of the wallet, its address if it has none
+    pub fn name(&self) -> String {
+        self.label
+            .clone()
+            .unwrap_or_else(|| self.address.to_string())
+    }
+}
+
+/// Parse a wallet given as `address` or `label=address`
+impl FromStr for Wallet {
+    type Err = eyre::Report;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let (label, address) = match value.split_once('=') {
+            Some((label, address)) => (Some(label.trim()), address.trim()),
+            None => (None, value.trim()),
+        };
+
+        // Labels end up in file names, so they are restricted to characters safe in a path.
+        if let Some(label) = label {
+            if label.is_empty()
+                || !label
+                    .chars()
+                    .all(|char| char.is_ascii_alphanumeric() || "-_.".contains(char))
+            {
+                return Err(eyre!(
+                    "Invalid wallet label \"{}\", expected letters, digits, \"-\", \"_\" or \".\"",
+                    label
+                ));
+            }
+        }
+
+        Ok(Self {
+            address: address
+                .parse()
+                .map_err(|_| eyre!("Failed to parse wallet address \"{}\"", address))?,
+            label: label.map(str::to_string),
+        })
+    }
+}
+
+/// Parse a comma-separated list of wallets, rejecting duplicate addresses and names
+pub fn parse_wallets(value: &str) -> Result<Vec<Wallet>> {
+    let wallets = value
+        .split(',')
+        .filter(|wallet| !wallet.trim().is_empty())
+        .map(str::parse)
+        .collect::<Result<Vec<Wallet>>>()?;
+
+    if wallets.is_empty() {
+        return Err(eyre!("No wallet address given"));
+    }
+
+    let mut addresses = HashSet::new();
+    let mut names = HashSet::new();
+
+    for wallet in &wallets {
+        if !addresses.insert(wallet.address) || !names.insert(wallet.name()) {
+            return Err(eyre!("Wallet {} is given more than once", wallet.name()));
+        }
+    }
+
+    Ok(wallets)
+}
+
+/// How the rows of several wallets are written
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WalletOutput {
+    /// A single CSV file with a Wallet column
+    #[default]
+    Combined,
+    /// One CSV file per wallet, named after the wallet
+    Split,
+}
+
+impl FromStr for WalletOutput {
+    type Err = eyre::Report;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value {
+            "combined" => Ok(Self::Combined),
+            "split" => Ok(Self::Split),
+            _ => Err(eyre!(
+                "Failed to parse \"WALLET_OUTPUT\", expected \"combined\" or \"split\""
+            )),
+        }
+    }
+}
+
+/// Which address of a liquidation is matched against the configured wallets
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MatchOn {
+    /// Address that sent the liquidation transaction
+    #[default]
+    Sender,
+    /// Address that received the liquidated asset
+    Receiver,
+    /// Either of them, the sender taking precedence when both are configured wallets, the
+    /// receiver then getting its own row of the tokens received
+    Either,
+}
+
+impl MatchOn {
+    /// Wallet a liquidation belongs to, `None` if none of its matched addresses is a wallet
+    pub fn wallet(
+        &self,
+        wallets: &[Address],
+        sender: Address,
+        receiver: Address,
+    ) -> Option<Address> {
+        let sender = (*self != MatchOn::Receiver && wallets.contains(&sender)).then_some(sender);
+        let receiver =
+            (*self != MatchOn::Sender && wallets.contains(&receiver)).then_some(receiver);
+
+        sender.or(receiver)
+    }
+
+    /// Filters returning the liquidations of the wallets, with the wallets set as `sender`
+    /// and/or `receiver` topics of a `SwappedExactAmountOut` event, the `receiver` topic
+    /// following the `sender` one. Topics can only be combined with AND, so matching on either
+    /// address requires one filter per topic.
+    pub fn filters(&self, filter: Filter, wallets: &[Address], sender_topic: usize) -> Vec<Filter> {
+        let topics: Vec<_> = wallets.iter().map(|wallet| wallet.into_word()).collect();
+        let with_topic = |mut filter: Filter, index: usize| {
+            filter.topics[index] = topics.clone().into();
+            filter
+        };
+
+        match self {
+            MatchOn::Sender => vec![with_topic(filter, sender_topic)],
+            MatchOn::Receiver => vec![with_topic(filter, sender_topic + 1)],
+            MatchOn::Either => vec![
+                with_topic(filter.clone(), sender_topic),
+                with_topic(filter, sender_topic + 1),
+            ],
+        }
+    }
+}
+
+impl FromStr for MatchOn {
+    type Err = eyre::Report;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value {
+            "sender" => Ok(Self::Sender),
+            "receiver" => Ok(Self::Receiver),
+            "either" => Ok(Self::Either),
+            _ => Err(eyre!(
+                "Failed to parse \"MATCH_ON\", expected \"sender\", \"receiver\" or \"either\""
+            )),
+        }
+    }
+}
+
+/// Activity of the wallets that is exported
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExportMode {
+    /// Liquidations, reverted liquidations, prize claiming fees and draw rewards of bots
+    #[default]
+    Bot,
+    /// Prizes won, vault deposits and vault withdrawals of depositors
+    Depositor,
+}
+
+impl FromStr for ExportMode {
+    type Err = eyre::Report;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value {
+            "bot" => Ok(Self::Bot),
+            "depositor" => Ok(Self::Depositor),
+            _ => Err(eyre!(
+                "Failed to parse \"EXPORT_MODE\", expected \"bot\" or \"depositor\""
+            )),
+        }
+    }
+}
+
+/// Koinly CSV file of an export and the wallets whose rows it receives
+#[derive(Clone, Debug)]
+pub struct ExportFile {
+    pub path: String,
+    pub wallets: Vec<Wallet>,
+    /// Whether rows are written with a Wallet column, only for files combining several wallets
+    pub wallet_column: bool,
+}
+
+impl ExportFile {
+    /// Files written for the given wallets, `output_path` being the path of the combined file
+    pub fn for_wallets(output_path: &str, wallets: &[Wallet], output: WalletOutput) -> Vec<Self> {
+        match output {
+            WalletOutput::Combined => vec![Self {
+                path: output_path.to_string(),
+                wallets: wallets.to_vec(),
+                wallet_column: wallets.len() > 1,
+            }],
+            WalletOutput::Split => wallets
+                .iter()
+                .map(|wallet| Self {
+                    path: format!(
+                        "{}-{}.csv",
+                        output_path.trim_end_matches(".csv"),
+                        wallet.name()
+                    ),
+                    wallets: vec![wallet.clone()],
+                    wallet_column: false,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use alloy::{primitives::address, rpc::types::Topic};
+
+    const HOT: Address = address!("00000000000000000000000000000000000000aa");
+    const COLD: Address = address!("00000000000000000000000000000000000000bb");
+    const OTHER: Address = address!("00000000000000000000000000000000000000cc");
+
+    #[test]
+    fn matches_the_wallet_of_liquidations() {
+        let wallets = [HOT, COLD];
+
+        assert_eq!(MatchOn::Sender.wallet(&wallets, HOT, COLD), Some(HOT));
+        assert_eq!(MatchOn::Sender.wallet(&wallets, OTHER, COLD), None);
+        assert_eq!(MatchOn::Receiver.wallet(&wallets, HOT, COLD), Some(COLD));
+        assert_eq!(MatchOn::Receiver.wallet(&wallets, COLD, OTHER), None);
+        assert_eq!(MatchOn::Either.wallet(&wallets, HOT, COLD), Some(HOT));
+        assert_eq!(MatchOn::Either.wallet(&wallets, OTHER, COLD), Some(COLD));
+        assert_eq!(MatchOn::Either.wallet(&wallets, OTHER, OTHER), None);
+    }
+
+    #[test]
+    fn sets_the_wallets_as_topics_of_the_filters() {
+        let wallets = [HOT, COLD];
+        let topic: Topic = vec![HOT.into_word(), COLD.into_word()].into();
+        // Indexes of the topics holding the wallets in each filter
+        let wallet_topics = |match_on: MatchOn| {
+            match_on
+                .filters(Filter::new(), &wallets, 2)
+                .iter()
+                .map(|filter| {
+                    (0..4)
+                        .filter(|index| !filter.topics[*index].is_empty())
+                        .inspect(|index| assert_eq!(filter.topics[*index], topic))
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(wallet_topics(MatchOn::Sender), vec![vec![2]]);
+        assert_eq!(wallet_topics(MatchOn::Receiver), vec![vec![3]]);
+        assert_eq!(wallet_topics(MatchOn::Either), vec![vec![2], vec![3]]);
+    }
+
+    #[test]
+    fn parses_labeled_wallets() {
+        let wallets = parse_wallets(&format!("{}, cold = {},", HOT, COLD)).unwrap();
+
+        assert_eq!(
+            wallets,
+            vec![
+                Wallet {
+                    address: HOT,
+                    label: None
+                },
+                Wallet {
+                    address: COLD,
+                    label: Some("cold".to_string())
+                }
+            ]
+        );
+        assert_eq!(wallets[0].name(), HOT.to_string());
+        assert_eq!(wallets[1].name(), "cold");
+    }
+
+    #[test]
+    fn rejects_invalid_wallets() {
+        assert!(parse_wallets("").is_err());
+        assert!(parse_wallets(&format!("../cold={}", COLD)).is_err());
+        assert!(parse_wallets(&format!("={}", COLD)).is_err());
+        assert!(parse_wallets("cold=0x1234").is_err());
+        assert!(parse_wallets(&format!("{},{}", HOT, HOT)).is_err());
+        assert!(parse_wallets(&format!("cold={},cold={}", HOT, COLD)).is_err());
+    }
+}